@@ -2,16 +2,46 @@
 # Make sure to set SORRY_BIN to the path where 'sorry' binary is installed
 # Example: export SORRY_BIN="$HOME/.cargo/bin/sorry"
 
+# Record each finished command with its directory and exit status, so sorry
+# knows where you were when things went wrong. Runs first to see the real $?.
+# New commands are spotted by their history number, so with HISTCONTROL set to
+# ignoredups or erasedups a command repeated right after itself isn't recorded
+# again (sorry then sees the earlier run of it).
+__sorry_record() {
+  local exit_status=$?
+  local entry
+  entry=$(HISTTIMEFORMAT= history 1)
+  if [[ "$entry" =~ ^[[:space:]]*([0-9]+)[[:space:]]+(.*)$ ]]; then
+    local number="${BASH_REMATCH[1]}"
+    # Skip the first prompt and empty Enter presses (history number unchanged)
+    # Recorded in the background so the prompt never waits on it
+    if [[ -n "$__sorry_last_number" && "$number" != "$__sorry_last_number" ]]; then
+      ( "${SORRY_BIN:-$HOME/.cargo/bin/sorry}" record \
+          --session "$$" \
+          --cwd "${__sorry_last_pwd:-$PWD}" \
+          --exit "$exit_status" \
+          -- "${BASH_REMATCH[2]}" >/dev/null 2>&1 & )
+    fi
+    __sorry_last_number="$number"
+  fi
+  # The command ran in the directory of the previous prompt
+  __sorry_last_pwd="$PWD"
+  return $exit_status
+}
+
 # Ensure history is written immediately
-export PROMPT_COMMAND='history -a; history -n; '"$PROMPT_COMMAND"
+export PROMPT_COMMAND='__sorry_record; history -a; history -n; '"$PROMPT_COMMAND"
 
 sorry() {
-  local count=10
+  # Send a generous window; sorry trims it to the configured history count
+  local count=50
+  local count_args=()
   local args=("$@")
   
   # Check if first argument is a number (count override)
   if [[ "$1" =~ ^[0-9]+$ ]]; then
     count="$1"
+    count_args=(--history-count "$1")
     args=("${args[@]:1}")  # Remove first arg
   fi
 
@@ -28,8 +58,9 @@ sorry() {
   local sorry_bin="${SORRY_BIN:-$HOME/.cargo/bin/sorry}"
   
   # Call the Rust binary with history commands
-  "$sorry_bin" \
+  SORRY_SESSION="$$" "$sorry_bin" \
     --shell bash \
     --last-commands "$last_cmds" \
+    "${count_args[@]}" \
     "${args[@]}"
 }
@@ -1,7 +1,31 @@
 use serde::{Deserialize, Serialize};
 
-use crate::config::load_config;
-use crate::history::{format_history_context, get_last_commands};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::time::Instant;
+
+use crate::budget::{estimate_tokens, fit_history, fit_section, Keep};
+use crate::cache::{cache_get, cache_key, cache_put};
+use crate::context::{error_excerpt, format_captured_output, CapturedOutput};
+use crate::collectors::{collect_provider_context, collect_runtime_context};
+use crate::config::{
+    expand_home, is_secret_header, load_config, Config, NetworkConfig, ProviderConfig,
+};
+use crate::environment::{environment_description, format_environment};
+use crate::files::{detect_file_refs, format_file_excerpts, merge_file_refs, FileRef};
+use crate::fixes::{error_signature, find_fix, load_fixes};
+use crate::history::{format_history_context, load_history, HistoryEntry};
+use crate::hooks::{run_post_response_hooks, run_pre_request_hooks};
+use crate::investigate::{investigate_prompt, run_tool, tool_definitions, MAX_INVESTIGATE_STEPS};
+use crate::journal::{append_log_entry, NewLogEntry};
+use crate::output::JSON_PROMPT;
+use crate::redact::redact;
+use crate::rules::{find_matching_rule, load_rules};
+use crate::runbooks::{format_runbooks, load_runbooks, select_runbooks};
+use crate::secrets::resolve_api_key;
+use crate::toolchain::{detect_project, format_project_hints, format_project_summary};
+use crate::usage::{check_spend_cap, record_usage, unpriced_cap_warning, Usage};
 
 // ============================================================================
 // OpenAI-compatible API types
@@ -10,13 +34,57 @@ use crate::history::{format_history_context, get_last_commands};
 #[derive(Debug, Serialize)]
 struct ChatMessage {
     role: String,
-    content: String,
+    /// Empty on assistant messages that only call tools
+    content: Option<String>,
+    /// Tool calls made by the assistant (`--investigate`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<ToolCall>,
+    /// On `tool` messages: which call this is the result of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    fn new(role: &str, content: String) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: Some(content),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
 struct ChatRequest {
     model: String,
     messages: Vec<ChatMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+    /// Function definitions offered with `--investigate`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<String>,
+}
+
+/// Serialize the request and merge the provider's `extra_body` on top of it.
+/// Extra fields win, so they can override anything sorry sends by default.
+fn request_body_json(
+    request: &ChatRequest,
+    extra_body: &serde_json::Map<String, serde_json::Value>,
+) -> Result<serde_json::Value, serde_json::Error> {
+    let mut body = serde_json::to_value(request)?;
+    if let serde_json::Value::Object(map) = &mut body {
+        for (key, value) in extra_body {
+            map.insert(key.clone(), value.clone());
+        }
+    }
+    Ok(body)
 }
 
 #[derive(Debug, Deserialize)]
@@ -26,12 +94,37 @@ struct ChatChoice {
 
 #[derive(Debug, Deserialize)]
 struct ChatResponseMessage {
-    content: String,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ToolCall>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ToolCall {
+    id: String,
+    #[serde(rename = "type", default = "function_type")]
+    kind: String,
+    function: ToolCallFunction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ToolCallFunction {
+    name: String,
+    /// JSON-encoded arguments, as the model wrote them
+    #[serde(default)]
+    arguments: String,
+}
+
+fn function_type() -> String {
+    "function".to_string()
 }
 
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     choices: Vec<ChatChoice>,
+    #[serde(default)]
+    usage: Option<Usage>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -44,14 +137,234 @@ struct ApiErrorDetail {
     message: String,
 }
 
+// ============================================================================
+// HTTP client
+// ============================================================================
+
+/// Build a blocking client honouring the timeouts, proxy and TLS settings
+fn build_client(network: &NetworkConfig) -> Result<reqwest::blocking::Client, Box<dyn std::error::Error>> {
+    let mut builder = reqwest::blocking::Client::builder()
+        .connect_timeout(network.connect_timeout())
+        .timeout(network.request_timeout());
+
+    if let Some(proxy) = &network.proxy {
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| format!("Invalid proxy URL '{}': {}", proxy, e))?;
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(path) = &network.ca_cert {
+        let path = expand_home(path);
+        let pem = fs::read(&path)
+            .map_err(|e| format!("Failed to read CA certificate '{}': {}", path.display(), e))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("Invalid CA certificate '{}': {}", path.display(), e))?;
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    if network.insecure_skip_verify {
+        eprintln!("Warning: TLS certificate verification is disabled (network.insecure_skip_verify).");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    Ok(builder.build()?)
+}
+
 // ============================================================================
 // LLM API call
 // ============================================================================
 
-pub fn call_llm(prompt: &str, provided_commands: Option<&str>) -> Result<String, Box<dyn std::error::Error>> {
-    let config = load_config();
+/// Per-invocation options from the command line
+#[derive(Debug, Default)]
+pub struct AskOptions {
+    /// Newline-separated commands passed in by the shell function
+    pub last_commands: Option<String>,
+    /// Explicit history count (`--history-count`; the shell function turns `sorry 5 ...` into it), overriding the config
+    pub history_count: Option<usize>,
+    /// Skip the fix library and local rules, always ask the LLM
+    pub no_local: bool,
+    /// Ignore cached answers (a fresh answer is still cached)
+    pub no_cache: bool,
+    /// Ask the model for JSON (`--format json`)
+    pub json: bool,
+    /// Output piped in on stdin or captured from tmux
+    pub input: Option<CapturedOutput>,
+    /// Files attached with --file
+    pub files: Vec<FileRef>,
+    /// Show the assembled request instead of sending it
+    pub dry_run: bool,
+    /// Let the model call read-only tools before answering
+    pub investigate: bool,
+}
+
+/// Where an answer came from
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnswerSource {
+    Llm,
+    /// Served from the response cache
+    Cache,
+    /// Answered offline by the named local rule
+    LocalRule(String),
+    /// A previously 👍'd answer for a similar error
+    FixLibrary,
+    /// `--dry-run`: the text is the request that would have been sent
+    DryRun,
+}
+
+#[derive(Debug, Clone)]
+pub struct Answer {
+    pub text: String,
+    pub source: AnswerSource,
+    /// Provider and model that produced the answer (None for local rules)
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// Tokens used, when the provider reported them
+    pub usage: Option<Usage>,
+    pub latency_ms: u64,
+}
+
+pub fn call_llm(prompt: &str, options: &AskOptions) -> Result<Answer, Box<dyn std::error::Error>> {
+    let config = load_config()?;
+    let started = Instant::now();
+
+    // Get terminal history context: the hooks' command log, the shell function's list, or the history file
+    let history_count = options.history_count.unwrap_or(config.history_count());
+    let mut commands = load_history(options.last_commands.as_deref(), history_count);
+    let start = commands.len().saturating_sub(history_count);
+    commands.drain(..start);
+
+    let last_command = commands.last().map(|c| c.command.as_str()).unwrap_or_default();
+    let excerpt = options.input.as_ref().map(|i| error_excerpt(&i.text)).unwrap_or_default();
+    let signature = error_signature(last_command, &excerpt);
+
+    // Explicit --file first, then files the error output points at
+    let mut file_refs = options.files.clone();
+    file_refs.extend(detect_file_refs(&format!(
+        "{}\n{}",
+        prompt,
+        options.input.as_ref().map(|i| i.text.as_str()).unwrap_or_default()
+    )));
+    let file_refs = merge_file_refs(file_refs);
+
+    let mut answer = answer_question(&config, prompt, &commands, &file_refs, &signature, options)?;
+    answer.latency_ms = started.elapsed().as_millis() as u64;
 
-    let provider_name = config.provider.ok_or(
+    if config.log.enabled && answer.source != AnswerSource::DryRun {
+        let entry = NewLogEntry {
+            question: prompt,
+            context: &format!(
+                "{}{}{}",
+                format_history_context(&commands),
+                options
+                    .input
+                    .as_ref()
+                    .map(|i| format_captured_output(i.source, &i.text))
+                    .unwrap_or_default(),
+                format_attached_files(&file_refs)
+            ),
+            signature: &signature,
+            answer: &answer,
+        };
+        if let Err(e) = append_log_entry(&config.log, &entry) {
+            eprintln!("Warning: failed to write to the log: {}", e);
+        }
+    }
+
+    Ok(answer)
+}
+
+/// The request as it would be sent, with the API key left out and messages shown as plain text
+fn format_dry_run(
+    url: &str,
+    headers: &HashMap<String, String>,
+    body: &serde_json::Value,
+) -> Result<String, serde_json::Error> {
+    let mut out = format!(
+        "POST {}\nAuthorization: Bearer [hidden]\nContent-Type: application/json\n",
+        url
+    );
+    for (name, value) in headers {
+        let value = if is_secret_header(name) {
+            "[hidden]"
+        } else {
+            value.as_str()
+        };
+        out.push_str(&format!("{}: {}\n", name, value));
+    }
+
+    let mut fields = body.clone();
+    let messages = fields
+        .as_object_mut()
+        .and_then(|map| map.remove("messages"))
+        .unwrap_or_default();
+    out.push_str(&format!("\n{}\n", serde_json::to_string_pretty(&fields)?));
+
+    let mut total = 0;
+    for message in messages.as_array().into_iter().flatten() {
+        let role = message["role"].as_str().unwrap_or_default();
+        let content = message["content"].as_str().unwrap_or_default();
+        let tokens = estimate_tokens(content);
+        total += tokens;
+        out.push_str(&format!("\n--- {} (~{} tokens) ---\n{}\n", role, tokens, content));
+    }
+    out.push_str(&format!("\n(~{} tokens in total, not sent)", total));
+    Ok(out)
+}
+
+/// One line naming the attached files, for the log
+fn format_attached_files(file_refs: &[FileRef]) -> String {
+    if file_refs.is_empty() {
+        return String::new();
+    }
+    let labels: Vec<String> = file_refs.iter().map(FileRef::label).collect();
+    format!("Attached files: {}\n", labels.join(", "))
+}
+
+fn answer_question(
+    config: &Config,
+    prompt: &str,
+    commands: &[HistoryEntry],
+    file_refs: &[FileRef],
+    signature: &str,
+    options: &AskOptions,
+) -> Result<Answer, Box<dyn std::error::Error>> {
+    // Answer instantly and offline from the fix library or a local rule
+    if !options.no_local && !options.dry_run && !options.investigate {
+        // Only error output identifies a fix; without it there is nothing to match on
+        let fix = if signature.is_empty() {
+            None
+        } else {
+            find_fix(&load_fixes()?, signature)
+        };
+        if let Some(fix) = fix {
+            return Ok(Answer {
+                text: fix.response,
+                source: AnswerSource::FixLibrary,
+                provider: None,
+                model: None,
+                usage: None,
+                latency_ms: 0,
+            });
+        }
+
+        let rules = load_rules()?;
+        let last_command = commands.last().map(|c| c.command.as_str()).unwrap_or_default();
+        let input = options.input.as_ref().map(|i| i.text.as_str()).unwrap_or_default();
+        if let Some(rule) = find_matching_rule(&rules, &[prompt, last_command, input]) {
+            return Ok(Answer {
+                text: rule.fix.clone(),
+                source: AnswerSource::LocalRule(rule.name.clone()),
+                provider: None,
+                model: None,
+                usage: None,
+                latency_ms: 0,
+            });
+        }
+    }
+
+    let provider_name = config.provider.clone().ok_or(
         "No provider configured. Run 'sorry --config-openai' or 'sorry --config-groq' first."
     )?;
 
@@ -60,56 +373,263 @@ pub fn call_llm(prompt: &str, provided_commands: Option<&str>) -> Result<String,
         provider_name
     ))?;
 
-    if provider.api_key.is_empty() {
-        return Err(format!(
-            "API key not set for provider '{}'. Run 'sorry --config-{}' to configure.",
-            provider_name, provider_name
-        )
-        .into());
+    let budget = &config.budget;
+
+    // The whole system prompt stays within system_tokens. Output format instructions are
+    // reserved first, since the answer can't be used without them; the other sections
+    // follow in order of importance, each cut to the room that is left.
+    let mut instructions = String::new();
+    if options.investigate {
+        instructions.push_str(&investigate_prompt());
+    }
+    if options.json {
+        instructions.push_str(JSON_PROMPT);
     }
+    let system_budget = budget.system_tokens().saturating_sub(estimate_tokens(&instructions));
 
-    let mood = config.mood.unwrap_or_default();
-    let system_prompt = mood.system_prompt(); // Returns String now
+    let project = config
+        .context
+        .project
+        .then(|| env::current_dir().ok().and_then(|cwd| detect_project(&cwd)))
+        .flatten();
+
+    let mut sections = vec![config.system_prompt()];
+    if config.context.environment {
+        sections.push(format_environment(&environment_description(&config.context)));
+    }
+    if let Some(project) = &project {
+        sections.push(format_project_hints(project));
+    }
+    if let Some(dir) = &config.runbooks_dir {
+        let runbooks = load_runbooks(&expand_home(dir))?;
+        let command_lines: Vec<String> = commands.iter().map(|c| c.command.clone()).collect();
+        sections.push(format_runbooks(&select_runbooks(&runbooks, prompt, &command_lines)));
+    }
+
+    let mut system_prompt = String::new();
+    for section in sections.iter().filter(|s| !s.is_empty()) {
+        let room = system_budget.saturating_sub(estimate_tokens(&system_prompt));
+        if room == 0 {
+            break;
+        }
+        system_prompt.push_str(&fit_section(section, room, Keep::Start));
+    }
+    system_prompt.push_str(&instructions);
+
+    // The rest of the total goes to the user message: the project summary and the question
+    // first, then environment state, captured output, files and history as room allows
+    let project_summary = project.as_ref().map(format_project_summary).unwrap_or_default();
+    let remaining = budget.total_tokens().saturating_sub(
+        estimate_tokens(&system_prompt) + estimate_tokens(prompt) + estimate_tokens(&project_summary),
+    );
+    let runtime_context = fit_section(
+        &format!(
+            "{}{}",
+            collect_runtime_context(&config.context, commands),
+            collect_provider_context(&config.context_providers, commands)?
+        ),
+        remaining,
+        Keep::Start,
+    );
+
+    // Captured output keeps its end, where the error usually is; secrets never leave the machine
+    let remaining = remaining.saturating_sub(estimate_tokens(&runtime_context));
+    let input_context = options
+        .input
+        .as_ref()
+        .map(|input| {
+            let text = fit_section(&redact(&input.text), budget.output_tokens().min(remaining), Keep::End);
+            format_captured_output(input.source, &text)
+        })
+        .unwrap_or_default();
+
+    let remaining = remaining.saturating_sub(estimate_tokens(&input_context));
+    let files_context = format_file_excerpts(file_refs, budget.file_tokens().min(remaining));
+
+    // Whatever is left over goes to history, oldest dropped first
+    let remaining = remaining.saturating_sub(estimate_tokens(&files_context));
+    let commands = fit_history(commands, budget, budget.history_tokens().min(remaining));
 
-    // Get terminal history context - use provided commands if available, otherwise read from file
-    let commands = if let Some(cmd_str) = provided_commands {
-        crate::history::parse_commands_from_string(cmd_str)
-    } else {
-        get_last_commands(10)
-    };
-    
     let history_context = format_history_context(&commands);
 
-    // Build user message with history context
-    let user_message = if history_context.is_empty() {
+    // Build user message with project, history, piped output and files as separate, labelled blocks
+    let context = format!(
+        "{}{}{}{}{}",
+        project_summary, history_context, runtime_context, input_context, files_context
+    );
+    let user_message = if context.is_empty() {
         prompt.to_string()
     } else {
-        format!("{}My question/problem: {}", history_context, prompt)
+        format!("{}My question/problem: {}", context, prompt)
     };
 
     let url = format!("{}/chat/completions", provider.base_url);
 
-    let request_body = ChatRequest {
+    let mut request = ChatRequest {
         model: provider.model.clone(),
         messages: vec![
-            ChatMessage {
-                role: "system".to_string(),
-                content: system_prompt,
-            },
-            ChatMessage {
-                role: "user".to_string(),
-                content: user_message,
-            },
+            ChatMessage::new("system", system_prompt),
+            ChatMessage::new("user", user_message),
         ],
+        temperature: provider.temperature,
+        max_tokens: provider.max_tokens,
+        response_format: (options.json && provider.supports_json_mode(&provider_name))
+            .then(|| serde_json::json!({ "type": "json_object" })),
+        tools: options.investigate.then(tool_definitions),
+        tool_choice: None,
     };
+    // Hooks run before the cache, so a policy hook can't be skipped by a cached answer
+    let mut request_body = run_pre_request_hooks(
+        &config.hooks.pre_request,
+        &provider_name,
+        request_body_json(&request, &provider.extra_body)?,
+    )?;
 
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", provider.api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()?;
+    let cache_key = cache_key(&provider_name, &request_body);
+    if config.cache.enabled && !options.no_cache && !options.dry_run && !options.investigate {
+        if let Some(text) = cache_get(&config.cache, &cache_key) {
+            return Ok(Answer {
+                text,
+                source: AnswerSource::Cache,
+                provider: Some(provider_name),
+                model: Some(provider.model.clone()),
+                usage: None,
+                latency_ms: 0,
+            });
+        }
+    }
+
+    if options.dry_run {
+        return Ok(Answer {
+            text: format_dry_run(&url, &provider.headers, &request_body)?,
+            source: AnswerSource::DryRun,
+            provider: Some(provider_name),
+            model: Some(provider.model.clone()),
+            usage: None,
+            latency_ms: 0,
+        });
+    }
+
+    let api_key = resolve_api_key(&provider_name, provider)?;
+    let client = build_client(&config.network)?;
+    let cap = config.usage.monthly_cap;
+    if let Some(warning) = unpriced_cap_warning(cap, &provider_name, provider) {
+        eprintln!("Warning: {}", warning);
+    }
+
+    // Without --investigate this is a single request; with it, the model may call
+    // tools for a few rounds and every result goes back to it before the final answer.
+    // send_chat records each round's usage, so the cap is checked again before the next.
+    let mut usage: Option<Usage> = None;
+    let mut step = 0;
+    let content = loop {
+        check_spend_cap(cap)?;
+        let response = send_chat(&client, &url, &api_key, &provider_name, provider, &request_body)?;
+        match response.usage {
+            Some(step_usage) => {
+                let total = usage.get_or_insert_with(Usage::default);
+                total.prompt_tokens += step_usage.prompt_tokens;
+                total.completion_tokens += step_usage.completion_tokens;
+            }
+            None if cap.is_some() => eprintln!(
+                "Warning: {} returned no token usage, so this call doesn't count towards usage.monthly_cap",
+                provider_name
+            ),
+            None => {}
+        }
+        let message = response
+            .choices
+            .into_iter()
+            .next()
+            .map(|c| c.message)
+            .ok_or("No response from API")?;
+
+        if !options.investigate || message.tool_calls.is_empty() {
+            break message.content.ok_or("No response from API")?;
+        }
+        if step >= MAX_INVESTIGATE_STEPS {
+            break message.content.ok_or(format!(
+                "The model was still calling tools after {} steps; try again without --investigate.",
+                MAX_INVESTIGATE_STEPS
+            ))?;
+        }
+
+        step += 1;
+        let calls = message.tool_calls.clone();
+        request.messages.push(ChatMessage {
+            role: "assistant".to_string(),
+            content: message.content,
+            tool_calls: message.tool_calls,
+            tool_call_id: None,
+        });
+        for call in calls {
+            let run = run_tool(&call.function.name, &call.function.arguments);
+            eprintln!("[investigate {}/{}] {}", step, MAX_INVESTIGATE_STEPS, run.display);
+            let mut result = ChatMessage::new("tool", run.output);
+            result.tool_call_id = Some(call.id);
+            request.messages.push(result);
+        }
+        if step >= MAX_INVESTIGATE_STEPS {
+            // Out of steps: the next reply has to be the answer
+            request.tool_choice = Some("none".to_string());
+        }
+        request_body = run_pre_request_hooks(
+            &config.hooks.pre_request,
+            &provider_name,
+            request_body_json(&request, &provider.extra_body)?,
+        )?;
+    };
+
+    let content = run_post_response_hooks(
+        &config.hooks.post_response,
+        &provider_name,
+        &provider.model,
+        &request_body,
+        serde_json::json!({ "text": content, "usage": usage }),
+    )?;
+
+    // Investigations depend on the state of the machine, so they aren't cached
+    if config.cache.enabled && !options.investigate {
+        cache_put(&config.cache, &cache_key, &content);
+    }
+
+    Ok(Answer {
+        text: content,
+        source: AnswerSource::Llm,
+        provider: Some(provider_name),
+        model: Some(provider.model.clone()),
+        usage,
+        latency_ms: 0,
+    })
+}
+
+/// Send one request to the provider and parse the reply, recording its token usage
+fn send_chat(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    api_key: &str,
+    provider_name: &str,
+    provider: &ProviderConfig,
+    request_body: &serde_json::Value,
+) -> Result<ChatResponse, Box<dyn std::error::Error>> {
+    let mut request = client
+        .post(url)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .header("Content-Type", "application/json");
+    for (name, value) in &provider.headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+
+    let response = request
+        .json(request_body)
+        .send()
+        .map_err(|e| {
+            if e.is_timeout() {
+                format!("Request to {} timed out: {}", provider.base_url, e)
+            } else {
+                format!("Request to {} failed: {}", provider.base_url, e)
+            }
+        })?;
 
     let status = response.status();
     let body = response.text()?;
@@ -125,11 +645,11 @@ pub fn call_llm(prompt: &str, provided_commands: Option<&str>) -> Result<String,
     let chat_response: ChatResponse = serde_json::from_str(&body)
         .map_err(|e| format!("Failed to parse API response: {}. Body: {}", e, body))?;
 
-    let content = chat_response
-        .choices
-        .first()
-        .map(|c| c.message.content.clone())
-        .ok_or("No response from API")?;
+    if let Some(usage) = &chat_response.usage {
+        if let Err(e) = record_usage(provider_name, provider, usage) {
+            eprintln!("Warning: failed to record usage: {}", e);
+        }
+    }
 
-    Ok(content)
+    Ok(chat_response)
 }
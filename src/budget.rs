@@ -0,0 +1,151 @@
+use crate::config::BudgetConfig;
+use crate::history::{format_history_context, HistoryEntry};
+use crate::redact::redact;
+
+// ============================================================================
+// Token estimation and truncation
+// ============================================================================
+
+/// Rough token count: ~4 characters per token for English text and code
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Which end of a text to keep when truncating
+#[derive(Debug, Clone, Copy)]
+pub enum Keep {
+    /// Keep the beginning (commands: the program and first arguments matter most)
+    Start,
+    /// Keep the end (captured output: errors are usually at the bottom)
+    End,
+}
+
+/// Cut `text` to at most `max_chars` characters, the marker for what was removed included
+pub fn truncate_chars(text: &str, max_chars: usize, keep: Keep) -> String {
+    let total = text.chars().count();
+    if total <= max_chars {
+        return text.to_string();
+    }
+    // Sized for removing everything, so the marker never outgrows the room left for it
+    let marker_chars = "…[truncated  chars]".chars().count() + total.to_string().len();
+    let Some(kept) = max_chars.checked_sub(marker_chars) else {
+        // Too little room for a marker: plain cut
+        return match keep {
+            Keep::Start => text.chars().take(max_chars).collect(),
+            Keep::End => text.chars().skip(total - max_chars).collect(),
+        };
+    };
+    let removed = total - kept;
+    match keep {
+        Keep::Start => {
+            let head: String = text.chars().take(kept).collect();
+            format!("{}…[truncated {} chars]", head, removed)
+        }
+        Keep::End => {
+            let tail: String = text.chars().skip(removed).collect();
+            format!("[truncated {} chars]…{}", removed, tail)
+        }
+    }
+}
+
+/// Redact commands and truncate long ones, then drop the oldest until the history block
+/// fits `max_tokens`
+pub fn fit_history(commands: &[HistoryEntry], budget: &BudgetConfig, max_tokens: usize) -> Vec<HistoryEntry> {
+    let mut fitted: Vec<HistoryEntry> = commands
+        .iter()
+        .map(|entry| HistoryEntry {
+            command: truncate_chars(&redact(&entry.command), budget.entry_chars(), Keep::Start),
+            ..entry.clone()
+        })
+        .collect();
+
+    while !fitted.is_empty() && estimate_tokens(&format_history_context(&fitted)) > max_tokens {
+        fitted.remove(0);
+    }
+    fitted
+}
+
+/// Cut a free-form section (e.g. runbooks) to roughly `max_tokens`
+pub fn fit_section(text: &str, max_tokens: usize, keep: Keep) -> String {
+    if estimate_tokens(text) <= max_tokens {
+        return text.to_string();
+    }
+    truncate_chars(text, max_tokens * 4, keep)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str) -> HistoryEntry {
+        HistoryEntry {
+            command: command.to_string(),
+            cwd: None,
+            exit_status: None,
+        }
+    }
+
+    #[test]
+    fn short_text_is_untouched() {
+        assert_eq!(truncate_chars("git push", 8, Keep::Start), "git push");
+        assert_eq!(fit_section("git push", 2, Keep::End), "git push");
+    }
+
+    #[test]
+    fn truncation_keeps_the_requested_end_within_the_limit() {
+        let text = "a".repeat(100) + &"b".repeat(100);
+        let start = truncate_chars(&text, 50, Keep::Start);
+        assert!(start.starts_with("aaa"));
+        assert!(start.ends_with("chars]"));
+        assert_eq!(start.chars().count(), 50);
+
+        let end = truncate_chars(&text, 50, Keep::End);
+        assert!(end.starts_with("[truncated"));
+        assert!(end.ends_with("bbb"));
+        assert_eq!(end.chars().count(), 50);
+    }
+
+    #[test]
+    fn tiny_limits_cut_without_a_marker() {
+        assert_eq!(truncate_chars("abcdefghij", 3, Keep::Start), "abc");
+        assert_eq!(truncate_chars("abcdefghij", 3, Keep::End), "hij");
+    }
+
+    #[test]
+    fn fitted_sections_stay_within_budget() {
+        let text = "error: something failed\n".repeat(500);
+        for max_tokens in [10, 100, 1000] {
+            let fitted = fit_section(&text, max_tokens, Keep::End);
+            assert!(estimate_tokens(&fitted) <= max_tokens, "{}", max_tokens);
+        }
+    }
+
+    #[test]
+    fn history_drops_the_oldest_commands_first() {
+        let commands: Vec<HistoryEntry> = (1..=50)
+            .map(|i| entry(&format!("make target-{}", i)))
+            .collect();
+        let fitted = fit_history(&commands, &BudgetConfig::default(), 60);
+        assert!(!fitted.is_empty() && fitted.len() < commands.len());
+        assert_eq!(fitted.last().unwrap().command, "make target-50");
+        assert!(estimate_tokens(&format_history_context(&fitted)) <= 60);
+    }
+
+    #[test]
+    fn history_has_no_secrets() {
+        let commands = [entry("export OPENAI_API_KEY=sk-abcdefghijklmnop1234")];
+        let fitted = fit_history(&commands, &BudgetConfig::default(), 1000);
+        assert!(!fitted[0].command.contains("sk-abc"));
+    }
+
+    #[test]
+    fn history_cuts_long_commands() {
+        let budget = BudgetConfig {
+            entry_chars: Some(40),
+            ..BudgetConfig::default()
+        };
+        let fitted = fit_history(&[entry(&"x".repeat(1000))], &budget, 1000);
+        assert!(fitted[0].command.chars().count() <= 40);
+        assert!(fitted[0].command.ends_with("chars]"));
+    }
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::CacheConfig;
+
+// ============================================================================
+// On-disk response cache
+// ============================================================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Unix timestamp (seconds) when the answer was stored
+    created_at: u64,
+    response: String,
+}
+
+pub fn get_cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("sorry")
+        .join("responses")
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Hash everything that influences the answer into a cache key: the provider and the
+/// request body exactly as it would be sent (model, messages, sampling settings, extra_body)
+pub fn cache_key(provider: &str, request_body: &serde_json::Value) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(provider.as_bytes());
+    // Separator so the provider name can't run into the body
+    hasher.update([0u8]);
+    hasher.update(request_body.to_string().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Look up a fresh cached answer
+pub fn cache_get(config: &CacheConfig, key: &str) -> Option<String> {
+    let path = get_cache_dir().join(format!("{}.json", key));
+    let content = fs::read_to_string(&path).ok()?;
+    let entry: CacheEntry = serde_json::from_str(&content).ok()?;
+
+    if now_secs().saturating_sub(entry.created_at) > config.ttl().as_secs() {
+        let _ = fs::remove_file(&path);
+        return None;
+    }
+    Some(entry.response)
+}
+
+/// Store an answer, evicting the oldest entries beyond the size limit.
+/// Failures are ignored: the cache is an optimisation, never a reason to fail.
+pub fn cache_put(config: &CacheConfig, key: &str, response: &str) {
+    let dir = get_cache_dir();
+    if fs::create_dir_all(&dir).is_err() {
+        return;
+    }
+
+    let entry = CacheEntry {
+        created_at: now_secs(),
+        response: response.to_string(),
+    };
+    let Ok(content) = serde_json::to_string(&entry) else {
+        return;
+    };
+    let _ = fs::write(dir.join(format!("{}.json", key)), content);
+
+    evict(config);
+}
+
+fn evict(config: &CacheConfig) {
+    let Ok(entries) = fs::read_dir(get_cache_dir()) else {
+        return;
+    };
+
+    let mut files: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .collect();
+
+    let max_entries = config.max_entries();
+    if files.len() <= max_entries {
+        return;
+    }
+
+    files.sort();
+    for (_, path) in &files[..files.len() - max_entries] {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Delete every cached answer, returning how many were removed
+pub fn cache_clear() -> Result<usize, Box<dyn std::error::Error>> {
+    let dir = get_cache_dir();
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
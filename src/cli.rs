@@ -1,8 +1,11 @@
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Read, Write};
 
 use crate::config::{
-    default_base_url, default_model, load_config, save_config, Config, Mood, ProviderConfig,
+    default_base_url, default_model, expand_home, is_secret_header, load_config,
+    load_global_config, save_config, Config, Mood, ProviderConfig,
 };
+use crate::runbooks::load_runbooks;
+use crate::secrets::{describe_key_source, get_keystore_path, keystore_set};
 
 // ============================================================================
 // Interactive helpers
@@ -20,22 +23,24 @@ fn prompt_input(prompt: &str) -> String {
     read_line()
 }
 
+/// Prompt without echoing when attached to a terminal; read a plain line when piped
+fn prompt_secret(prompt: &str) -> io::Result<String> {
+    if io::stdin().is_terminal() {
+        rpassword::prompt_password(prompt).map(|s| s.trim().to_string())
+    } else {
+        Ok(read_line())
+    }
+}
+
 // ============================================================================
 // Configuration commands
 // ============================================================================
 
 pub fn configure_provider_interactive(provider: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let mut config = load_config();
-
-    // Ensure we have default provider configs
-    if config.providers.is_empty() {
-        config.providers = Config::default_providers();
-    }
-
     println!("\n🔧 Configuring {}\n", provider);
 
     // Step 1: Get API key
-    let api_key = prompt_input("Enter API key: ");
+    let api_key = prompt_secret("Enter API key: ")?;
     if api_key.is_empty() {
         return Err("API key cannot be empty.".into());
     }
@@ -50,30 +55,105 @@ pub fn configure_provider_interactive(provider: &str) -> Result<(), Box<dyn std:
         model_input
     };
 
-    // Update config
+    save_provider(provider, api_key, Some(model.clone()))?;
+
+    println!("\n✓ Configured {} with model '{}'", provider, model);
+    Ok(())
+}
+
+/// Non-interactive `--config-<provider>`: the API key is piped in on stdin
+pub fn configure_provider_from_stdin(provider: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    let api_key = input.lines().next().unwrap_or_default().trim().to_string();
+    if api_key.is_empty() {
+        return Err("No API key received on stdin.".into());
+    }
+
+    let model = save_provider(provider, api_key, None)?;
+
+    println!("✓ Configured {} with model '{}'", provider, model);
+    Ok(())
+}
+
+/// Store a provider's key (and optionally model) and make it the active provider.
+/// Returns the model now configured for the provider.
+fn save_provider(
+    provider: &str,
+    api_key: String,
+    model: Option<String>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut config = load_global_config()?;
+
+    // Ensure we have default provider configs
+    if config.providers.is_empty() {
+        config.providers = Config::default_providers();
+    }
+
     let provider_config = config
         .providers
         .entry(provider.to_string())
         .or_insert_with(|| ProviderConfig {
             api_key: String::new(),
             base_url: default_base_url(provider).to_string(),
-            model: default.to_string(),
+            model: default_model(provider).to_string(),
+            ..Default::default()
         });
 
     provider_config.api_key = api_key;
-    provider_config.model = model.clone();
+    if let Some(model) = model {
+        provider_config.model = model;
+    }
+    let model = provider_config.model.clone();
 
     // Set as active provider
     config.provider = Some(provider.to_string());
 
     save_config(&config)?;
+    Ok(model)
+}
 
-    println!("\n✓ Configured {} with model '{}'", provider, model);
+pub fn configure_keystore(provider: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = load_global_config()?;
+
+    if config.providers.is_empty() {
+        config.providers = Config::default_providers();
+    }
+
+    println!("\n🔐 Storing {} API key in the encrypted keystore\n", provider);
+
+    let api_key = prompt_secret("Enter API key: ")?;
+    if api_key.is_empty() {
+        return Err("API key cannot be empty.".into());
+    }
+
+    keystore_set(provider, &api_key)?;
+
+    let provider_config = config
+        .providers
+        .entry(provider.to_string())
+        .or_insert_with(|| ProviderConfig {
+            base_url: default_base_url(provider).to_string(),
+            model: default_model(provider).to_string(),
+            ..Default::default()
+        });
+
+    // Drop the plaintext copy now that the keystore has it
+    provider_config.api_key.clear();
+    provider_config.api_key_keystore = true;
+
+    if config.provider.is_none() {
+        config.provider = Some(provider.to_string());
+    }
+
+    save_config(&config)?;
+
+    println!("\n✓ Stored key for {} in {}", provider, get_keystore_path().display());
     Ok(())
 }
 
 pub fn configure_behaviour() -> Result<(), Box<dyn std::error::Error>> {
-    let mut config = load_config();
+    let mut config = load_global_config()?;
 
     println!("\n🎭 Configure sorry's behaviour\n");
     println!("Choose a mood:\n");
@@ -103,14 +183,52 @@ pub fn configure_behaviour() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-pub fn show_config() {
-    let config = load_config();
+pub fn show_config() -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config()?;
 
     println!();
     
+    // Show project overrides
+    if let Some(project) = &config.project {
+        println!("Project config: {}", project.path.display());
+        let mut overrides = Vec::new();
+        if project.mood.is_some() {
+            overrides.push("mood");
+        }
+        if project.provider.is_some() {
+            overrides.push("provider");
+        }
+        if project.model.is_some() {
+            overrides.push("model");
+        }
+        if project.history_count.is_some() {
+            overrides.push("history count");
+        }
+        if project.prompt_notes.is_some() {
+            overrides.push("prompt notes");
+        }
+        if !overrides.is_empty() {
+            println!("  Overrides: {}", overrides.join(", "));
+        }
+        if let Some(notes) = &project.prompt_notes {
+            for line in notes.trim().lines() {
+                println!("  | {}", line);
+            }
+        }
+        println!();
+    }
+
     // Show mood
     let mood = config.mood.unwrap_or_default();
     println!("Mood: {}", mood.display_name());
+    println!("History: last {} commands", config.history_count());
+    if let Some(dir) = &config.runbooks_dir {
+        let dir = expand_home(dir);
+        match load_runbooks(&dir) {
+            Ok(runbooks) => println!("Runbooks: {} ({} snippets)", dir.display(), runbooks.len()),
+            Err(e) => println!("Runbooks: {}", e),
+        }
+    }
     println!();
 
     // Show provider
@@ -120,12 +238,22 @@ pub fn show_config() {
             if let Some(pc) = config.providers.get(provider) {
                 println!("  Base URL: {}", pc.base_url);
                 println!("  Model: {}", pc.model);
-                let key_status = if pc.api_key.is_empty() {
-                    "not set"
-                } else {
-                    "configured (hidden)"
-                };
-                println!("  API Key: {}", key_status);
+                if let Some(temperature) = pc.temperature {
+                    println!("  Temperature: {}", temperature);
+                }
+                if let Some(max_tokens) = pc.max_tokens {
+                    println!("  Max tokens: {}", max_tokens);
+                }
+                if !pc.headers.is_empty() {
+                    let mut names: Vec<&String> = pc.headers.keys().collect();
+                    names.sort();
+                    let names: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+                    println!("  Extra headers: {}", names.join(", "));
+                }
+                if !pc.extra_body.is_empty() {
+                    println!("  Extra body: {}", serde_json::Value::Object(pc.extra_body.clone()));
+                }
+                println!("  API Key: {}", describe_key_source(pc));
             }
         }
         None => {
@@ -134,4 +262,265 @@ pub fn show_config() {
         }
     }
     println!();
+
+    // Show network settings
+    let network = &config.network;
+    println!("Network:");
+    println!(
+        "  Timeouts: connect {}s, request {}s",
+        network.connect_timeout().as_secs(),
+        network.request_timeout().as_secs()
+    );
+    if let Some(proxy) = &network.proxy {
+        println!("  Proxy: {}", proxy);
+    }
+    if let Some(ca_cert) = &network.ca_cert {
+        println!("  Extra CA certificates: {}", ca_cert.display());
+    }
+    if network.insecure_skip_verify {
+        println!("  TLS verification: DISABLED");
+    }
+    println!();
+
+    // Show cache settings
+    if config.cache.enabled {
+        println!(
+            "Cache: on (ttl {}s, up to {} answers)",
+            config.cache.ttl().as_secs(),
+            config.cache.max_entries()
+        );
+    } else {
+        println!("Cache: off");
+    }
+
+    // Show optional context
+    println!(
+        "Project detection: {}",
+        if config.context.project { "on" } else { "off" }
+    );
+    if config.context.environment {
+        println!(
+            "Environment context: on (refreshed every {}s)",
+            config.context.environment_ttl().as_secs()
+        );
+    } else {
+        println!("Environment context: off");
+    }
+    println!(
+        "Kubernetes context: {}, Docker context: {}",
+        if config.context.kubernetes { "on" } else { "off" },
+        if config.context.docker { "on" } else { "off" }
+    );
+    if !config.context_providers.is_empty() {
+        println!("Context providers:");
+        for provider in &config.context_providers {
+            println!("  {} (when /{}/): {}", provider.name, provider.when, provider.cmd);
+        }
+    }
+    for (event, hooks) in [
+        ("pre_request", &config.hooks.pre_request),
+        ("post_response", &config.hooks.post_response),
+    ] {
+        for hook in hooks {
+            let required = if hook.required { ", required" } else { "" };
+            println!("Hook ({}{}): {}: {}", event, required, hook.name, hook.cmd);
+        }
+    }
+    println!();
+    Ok(())
+}
+
+// ============================================================================
+// Non-interactive config commands (`sorry config ...`)
+// ============================================================================
+
+const HIDDEN: &str = "(hidden)";
+
+/// Split a dotted key like `providers.openai.model` into its segments
+fn key_segments(key: &str) -> Result<Vec<&str>, Box<dyn std::error::Error>> {
+    let segments: Vec<&str> = key.split('.').collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(format!("Invalid config key '{}'.", key).into());
+    }
+    Ok(segments)
+}
+
+fn config_to_table(config: &Config) -> Result<toml::Table, Box<dyn std::error::Error>> {
+    Ok(toml::Table::try_from(config)?)
+}
+
+/// Validate an edited table by turning it back into a `Config`
+fn table_to_config(table: toml::Table, key: &str) -> Result<Config, Box<dyn std::error::Error>> {
+    toml::Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| format!("Invalid value for '{}': {}", key, e.message()).into())
+}
+
+/// Mask API keys and credential headers so `config get`/`config list` never print secrets
+fn mask_secrets(value: &mut toml::Value) {
+    if let toml::Value::Table(table) = value {
+        for (name, child) in table.iter_mut() {
+            if name == "api_key" {
+                *child = toml::Value::String(HIDDEN.to_string());
+            } else if name == "headers" {
+                mask_headers(child);
+            } else {
+                mask_secrets(child);
+            }
+        }
+    }
+}
+
+/// Mask the values of credential headers in a `headers` table
+fn mask_headers(headers: &mut toml::Value) {
+    if let toml::Value::Table(headers) = headers {
+        for (name, value) in headers.iter_mut() {
+            if is_secret_header(name) {
+                *value = toml::Value::String(HIDDEN.to_string());
+            }
+        }
+    }
+}
+
+fn print_value(value: &toml::Value) -> Result<(), Box<dyn std::error::Error>> {
+    match value {
+        toml::Value::String(s) => println!("{}", s),
+        toml::Value::Table(table) => print!("{}", toml::to_string_pretty(table)?),
+        other => println!("{}", other),
+    }
+    Ok(())
+}
+
+/// Parse a command-line value as a TOML literal (`true`, `0.2`, `["a"]`), or `None` if it isn't one
+fn parse_literal(raw: &str) -> Option<toml::Value> {
+    let table: toml::Table = toml::from_str(&format!("value = {}", raw)).ok()?;
+    table.get("value").cloned()
+}
+
+pub fn config_get(key: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_global_config()?;
+    let table = config_to_table(&config)?;
+    let segments = key_segments(key)?;
+    let mut value = lookup(&table, &segments)
+        .cloned()
+        .ok_or(format!("'{}' is not set.", key))?;
+
+    let secret_header = matches!(segments.as_slice(), [.., "headers", name] if is_secret_header(name));
+    if (key.ends_with("api_key") || secret_header) && value.as_str().is_some_and(|s| !s.is_empty()) {
+        println!("{}", HIDDEN);
+        return Ok(());
+    }
+    if segments.last() == Some(&"headers") {
+        mask_headers(&mut value);
+    }
+    mask_secrets(&mut value);
+    print_value(&value)
+}
+
+pub fn config_list() -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_global_config()?;
+    let mut value = toml::Value::Table(config_to_table(&config)?);
+    mask_secrets(&mut value);
+    print_value(&value)
+}
+
+pub fn config_set(key: &str, raw_value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let segments = key_segments(key)?;
+    if segments == ["version"] {
+        return Err("'version' is managed by sorry and cannot be set.".into());
+    }
+
+    let mut config = load_global_config()?;
+
+    // Setting a field on an unknown provider creates it with sensible defaults
+    if let ["providers", name, _, ..] = segments.as_slice() {
+        config
+            .providers
+            .entry(name.to_string())
+            .or_insert_with(|| ProviderConfig {
+                base_url: default_base_url(name).to_string(),
+                model: default_model(name).to_string(),
+                ..Default::default()
+            });
+    }
+
+    let table = config_to_table(&config)?;
+
+    // Prefer a typed literal, but fall back to a plain string (e.g. model = "4o")
+    let mut candidates = Vec::new();
+    if let Some(literal) = parse_literal(raw_value) {
+        candidates.push(literal);
+    }
+    candidates.push(toml::Value::String(raw_value.to_string()));
+
+    let mut last_error = None;
+    for candidate in candidates {
+        let mut edited = table.clone();
+        insert_at(&mut edited, &segments, candidate);
+        match table_to_config(edited, key) {
+            Ok(new_config) => {
+                // Unknown fields are dropped on deserialization, so make sure the key survived
+                if lookup(&config_to_table(&new_config)?, &segments).is_none() {
+                    return Err(format!("Unknown config key '{}'.", key).into());
+                }
+                save_config(&new_config)?;
+                println!("✓ Set {}", key);
+                return Ok(());
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| format!("Invalid value for '{}'.", key).into()))
+}
+
+pub fn config_unset(key: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let segments = key_segments(key)?;
+    if segments == ["version"] {
+        return Err("'version' is managed by sorry and cannot be unset.".into());
+    }
+
+    let config = load_global_config()?;
+    let mut table = config_to_table(&config)?;
+
+    let (last, parents) = segments.split_last().ok_or("Empty config key.")?;
+    let mut current = &mut table;
+    for segment in parents {
+        current = current
+            .get_mut(*segment)
+            .and_then(|v| v.as_table_mut())
+            .ok_or(format!("'{}' is not set.", key))?;
+    }
+    if current.remove(*last).is_none() {
+        return Err(format!("'{}' is not set.", key).into());
+    }
+
+    let new_config = table_to_config(table, key)
+        .map_err(|_| format!("'{}' is required and cannot be unset.", key))?;
+    save_config(&new_config)?;
+    println!("✓ Unset {}", key);
+    Ok(())
+}
+
+fn lookup<'a>(table: &'a toml::Table, segments: &[&str]) -> Option<&'a toml::Value> {
+    let (first, rest) = segments.split_first()?;
+    rest.iter().try_fold(table.get(*first)?, |value, segment| value.get(*segment))
+}
+
+/// Insert a value at a dotted path, creating intermediate tables as needed
+fn insert_at(table: &mut toml::Table, segments: &[&str], value: toml::Value) {
+    let Some((last, parents)) = segments.split_last() else {
+        return;
+    };
+    let mut current = table;
+    for segment in parents {
+        let entry = current
+            .entry(segment.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+        current = entry.as_table_mut().expect("just ensured a table");
+    }
+    current.insert(last.to_string(), value);
 }
@@ -0,0 +1,196 @@
+use regex::Regex;
+use std::sync::LazyLock;
+use std::thread;
+use std::time::Duration;
+
+use crate::budget::{truncate_chars, Keep};
+use crate::config::{ContextConfig, ContextProviderConfig};
+use crate::history::HistoryEntry;
+use crate::probe::{first_line, run_with_timeout};
+use crate::redact::redact;
+
+// ============================================================================
+// Kubernetes and Docker context (opt-in)
+// ============================================================================
+
+/// Longest a single kubectl or docker call may take; both only read local state
+const COLLECTOR_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Most containers described
+const MAX_CONTAINERS: usize = 5;
+
+static KUBE_COMMAND: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(^|[\s;&|(])(kubectl|helm|kubectx|kubens|k9s|k)\s").expect("valid kube pattern")
+});
+
+/// `docker logs web`, `docker exec -it web sh`, `docker container restart web`, ...
+static DOCKER_CONTAINER_COMMAND: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"docker\s+(?:container\s+)?(?:logs|exec|start|stop|restart|rm|inspect|attach|kill|top|port|stats)\s+((?:(?:-e|--env|-u|--user|-w|--workdir|--tail|-n|--since|--until|-s|--signal|--time)[= ]\S+\s+|-{1,2}[\w-]+\s+)*)([\w][\w.-]*)",
+    )
+    .expect("valid docker pattern")
+});
+
+fn uses_kubernetes(commands: &[HistoryEntry]) -> bool {
+    commands
+        .iter()
+        .any(|c| KUBE_COMMAND.is_match(&format!("{} ", c.command)))
+}
+
+/// Container names or ids mentioned in recent docker commands, newest first
+fn referenced_containers(commands: &[HistoryEntry]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for entry in commands.iter().rev() {
+        for captures in DOCKER_CONTAINER_COMMAND.captures_iter(&entry.command) {
+            let name = captures[2].to_string();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names.truncate(MAX_CONTAINERS);
+    names
+}
+
+/// Current kube context and namespace, read from the kubeconfig without contacting the cluster
+fn kubernetes_context() -> Option<String> {
+    let context = first_line("kubectl", &["config", "current-context"], COLLECTOR_TIMEOUT)?;
+    let namespace = first_line(
+        "kubectl",
+        &["config", "view", "--minify", "-o", "jsonpath={..namespace}"],
+        COLLECTOR_TIMEOUT,
+    )
+    .unwrap_or_else(|| "default".to_string());
+    Some(format!(
+        "Kubernetes: context {}, namespace {}",
+        context, namespace
+    ))
+}
+
+/// Status of the given containers from `docker ps -a`
+fn docker_context(containers: &[String]) -> Option<String> {
+    let output = run_with_timeout(
+        "docker",
+        &[
+            "ps",
+            "-a",
+            "--format",
+            "{{.ID}}\t{{.Names}}\t{{.Image}}\t{{.Status}}",
+        ],
+        COLLECTOR_TIMEOUT,
+    )?;
+
+    let mut lines = Vec::new();
+    for wanted in containers {
+        let row = output.lines().find_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [id, name, image, status] = fields[..] else {
+                return None;
+            };
+            (name == wanted || id.starts_with(wanted.as_str()))
+                .then(|| format!("  {} ({}): {}", name, image, status))
+        });
+        lines.push(row.unwrap_or_else(|| format!("  {}: no such container", wanted)));
+    }
+    Some(format!("Docker containers:\n{}", lines.join("\n")))
+}
+
+/// Kubernetes and Docker state relevant to recent commands, or an empty string
+pub fn collect_runtime_context(config: &ContextConfig, commands: &[HistoryEntry]) -> String {
+    let want_kube = config.kubernetes && uses_kubernetes(commands);
+    let containers = if config.docker {
+        referenced_containers(commands)
+    } else {
+        Vec::new()
+    };
+    if !want_kube && containers.is_empty() {
+        return String::new();
+    }
+
+    // Both run at once, so the worst case is one timeout rather than their sum
+    let (kube, docker) = thread::scope(|scope| {
+        let kube = scope.spawn(|| want_kube.then(kubernetes_context).flatten());
+        let docker = scope.spawn(|| {
+            if containers.is_empty() {
+                None
+            } else {
+                docker_context(&containers)
+            }
+        });
+        (kube.join().ok().flatten(), docker.join().ok().flatten())
+    });
+
+    let sections: Vec<String> = [kube, docker].into_iter().flatten().collect();
+    if sections.is_empty() {
+        return String::new();
+    }
+    format!(
+        "Here is my current environment state:\n```\n{}\n```\n\n",
+        sections.join("\n")
+    )
+}
+
+// ============================================================================
+// External context providers (`context_providers` in the config)
+// ============================================================================
+
+/// Default time limit for a context provider command
+const DEFAULT_PROVIDER_TIMEOUT_SECS: u64 = 3;
+
+/// Default cap on a context provider's output, in characters
+const DEFAULT_PROVIDER_MAX_CHARS: usize = 2000;
+
+/// Run the configured providers whose `when` pattern matches a recent command,
+/// in parallel, and label their redacted, capped output for the prompt
+pub fn collect_provider_context(
+    providers: &[ContextProviderConfig],
+    commands: &[HistoryEntry],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut triggered = Vec::new();
+    for provider in providers {
+        let when = Regex::new(&provider.when).map_err(|e| {
+            format!("Invalid 'when' pattern for context provider '{}': {}", provider.name, e)
+        })?;
+        if commands.iter().any(|c| when.is_match(&c.command)) {
+            triggered.push(provider);
+        }
+    }
+    if triggered.is_empty() {
+        return Ok(String::new());
+    }
+
+    let outputs: Vec<(&ContextProviderConfig, Option<String>)> = thread::scope(|scope| {
+        let handles: Vec<_> = triggered
+            .iter()
+            .map(|provider| {
+                let timeout = Duration::from_secs(
+                    provider.timeout_secs.unwrap_or(DEFAULT_PROVIDER_TIMEOUT_SECS),
+                );
+                scope.spawn(move || run_with_timeout("sh", &["-c", &provider.cmd], timeout))
+            })
+            .collect();
+        triggered
+            .iter()
+            .zip(handles)
+            .map(|(provider, handle)| (*provider, handle.join().ok().flatten()))
+            .collect()
+    });
+
+    let mut context = String::new();
+    for (provider, output) in outputs {
+        let Some(output) = output.filter(|o| !o.trim().is_empty()) else {
+            eprintln!(
+                "Warning: context provider '{}' failed, timed out or printed nothing",
+                provider.name
+            );
+            continue;
+        };
+        let max_chars = provider.max_chars.unwrap_or(DEFAULT_PROVIDER_MAX_CHARS);
+        let output = truncate_chars(&redact(&output), max_chars, Keep::Start);
+        context.push_str(&format!(
+            "Here is the output of `{}` ({}):\n```\n{}\n```\n\n",
+            provider.cmd, provider.name, output
+        ));
+    }
+    Ok(context)
+}
@@ -1,17 +1,52 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 // ============================================================================
 // Config types
 // ============================================================================
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ProviderConfig {
+    /// Plaintext API key (prefer one of the sources below)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub api_key: String,
+    /// Read the API key from this environment variable, e.g. "OPENAI_API_KEY"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+    /// Read the API key from the first line of this command's output, e.g. "pass show openai"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_cmd: Option<String>,
+    /// Read the API key from the encrypted keystore
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub api_key_keystore: bool,
     pub base_url: String,
     pub model: String,
+    /// Sampling temperature sent with every request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Upper bound on completion tokens sent with every request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Request `response_format: json_object` for `--format json`
+    /// (default: on for openai and groq, off for other endpoints)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_mode: Option<bool>,
+    /// Price in USD per million prompt tokens, for `sorry usage`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_price: Option<f64>,
+    /// Price in USD per million completion tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_price: Option<f64>,
+    /// Extra HTTP headers, e.g. {"X-Team-Id": "infra"}
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// Free-form fields merged into the request body, e.g. {"top_p": 0.9}
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub extra_body: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
@@ -90,11 +125,352 @@ Be brutally honest and sassy. Roast their mistakes, call them "idiot", "dumbass"
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+/// HTTP client settings shared by all providers
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Seconds to wait for the TCP/TLS connection (default 10)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_secs: Option<u64>,
+    /// Seconds the whole request may take, from connecting to the end of the response
+    /// body (default 60). A total deadline, not a read timeout: a slow response that
+    /// keeps sending data is still cut off when it runs out.
+    #[serde(alias = "timeout_secs", skip_serializing_if = "Option::is_none")]
+    pub request_timeout_secs: Option<u64>,
+    /// Proxy URL used for all requests, e.g. "http://proxy.corp:3128"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Extra PEM-encoded root certificates to trust
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<PathBuf>,
+    /// Disable TLS certificate verification (local testing only!)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub insecure_skip_verify: bool,
+}
+
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 60;
+
+impl NetworkConfig {
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS))
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs.unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS))
+    }
+}
+
+/// Header names that usually carry credentials (`Authorization`, `X-Api-Key`, ...),
+/// whose values are never printed
+pub fn is_secret_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    ["auth", "key", "token", "secret", "cookie", "password"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+impl ProviderConfig {
+    pub fn supports_json_mode(&self, provider_name: &str) -> bool {
+        self.json_mode
+            .unwrap_or(matches!(provider_name, "openai" | "groq"))
+    }
+}
+
+/// Response cache settings
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    /// How long an answer stays valid, in seconds (default 1 hour)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+    /// Maximum number of cached answers; the oldest are evicted first (default 200)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_entries: Option<usize>,
+}
+
+pub const DEFAULT_CACHE_TTL_SECS: u64 = 60 * 60;
+pub const DEFAULT_CACHE_MAX_ENTRIES: usize = 200;
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            enabled: true,
+            ttl_secs: None,
+            max_entries: None,
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs.unwrap_or(DEFAULT_CACHE_TTL_SECS))
+    }
+
+    pub fn max_entries(&self) -> usize {
+        self.max_entries.unwrap_or(DEFAULT_CACHE_MAX_ENTRIES)
+    }
+}
+
+/// Persistent question/answer log settings
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LogConfig {
+    pub enabled: bool,
+    /// Oldest entries are dropped beyond this many (default 1000)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_entries: Option<usize>,
+}
+
+pub const DEFAULT_LOG_MAX_ENTRIES: usize = 1000;
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            enabled: true,
+            max_entries: None,
+        }
+    }
+}
+
+impl LogConfig {
+    pub fn max_entries(&self) -> usize {
+        self.max_entries.unwrap_or(DEFAULT_LOG_MAX_ENTRIES).max(1)
+    }
+}
+
+/// Extra context collected from the machine
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ContextConfig {
+    /// Detect the project type in the working directory (default on)
+    pub project: bool,
+    /// Describe the OS, shell, git and installed toolchains to the model (default off)
+    pub environment: bool,
+    /// How long that description is reused, in seconds (default 1 day)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment_ttl_secs: Option<u64>,
+    /// After kubectl/helm commands, include the current kube context and namespace (default off)
+    pub kubernetes: bool,
+    /// After docker commands, include the status of the containers they named (default off)
+    pub docker: bool,
+    /// Regex matching your shell prompt, used to find the last command in --from-tmux output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_pattern: Option<String>,
+}
+
+pub const DEFAULT_ENVIRONMENT_TTL_SECS: u64 = 24 * 60 * 60;
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        ContextConfig {
+            project: true,
+            environment: false,
+            environment_ttl_secs: None,
+            kubernetes: false,
+            docker: false,
+            prompt_pattern: None,
+        }
+    }
+}
+
+impl ContextConfig {
+    pub fn environment_ttl(&self) -> Duration {
+        Duration::from_secs(self.environment_ttl_secs.unwrap_or(DEFAULT_ENVIRONMENT_TTL_SECS))
+    }
+}
+
+/// An external command whose output is added to the prompt when `when` matches a recent command
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContextProviderConfig {
+    pub name: String,
+    /// Regex matched against each recent command, e.g. "terraform"
+    pub when: String,
+    /// Shell command to run, e.g. "terraform workspace show"
+    pub cmd: String,
+    /// Give up after this many seconds (default 3)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Keep at most this many characters of output (default 2000)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_chars: Option<usize>,
+}
+
+/// An external command that rewrites the request or the response, see `hooks.rs`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HookConfig {
+    pub name: String,
+    /// Shell command to run; it reads a JSON object on stdin and may print one back
+    pub cmd: String,
+    /// Stop with an error when the hook fails instead of carrying on without it
+    #[serde(default)]
+    pub required: bool,
+    /// Give up after this many seconds (default 5)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Hooks run in order around each LLM call
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Run on the outgoing request before it is sent
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pre_request: Vec<HookConfig>,
+    /// Run on each fresh answer from the LLM
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub post_response: Vec<HookConfig>,
+}
+
+impl HooksConfig {
+    pub fn is_empty(&self) -> bool {
+        self.pre_request.is_empty() && self.post_response.is_empty()
+    }
+}
+
+/// Token budgets for what gets sent to the model (estimated at ~4 chars per token)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Whole request: system prompt plus user message (default 6000)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_tokens: Option<usize>,
+    /// Whole system prompt: base prompt, project notes, environment, project hints and
+    /// runbooks (default 2000). `--format json`/`--investigate` instructions are never cut.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_tokens: Option<usize>,
+    /// Terminal history block (default 1500)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_tokens: Option<usize>,
+    /// Captured output such as piped stdin (default 2000)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_tokens: Option<usize>,
+    /// Files attached with --file or referenced in output (default 2000)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_tokens: Option<usize>,
+    /// Longest single history entry, in characters (default 500)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry_chars: Option<usize>,
+}
+
+impl BudgetConfig {
+    pub fn total_tokens(&self) -> usize {
+        self.total_tokens.unwrap_or(6000)
+    }
+
+    pub fn system_tokens(&self) -> usize {
+        self.system_tokens.unwrap_or(2000)
+    }
+
+    pub fn history_tokens(&self) -> usize {
+        self.history_tokens.unwrap_or(1500)
+    }
+
+    pub fn output_tokens(&self) -> usize {
+        self.output_tokens.unwrap_or(2000)
+    }
+
+    pub fn file_tokens(&self) -> usize {
+        self.file_tokens.unwrap_or(2000)
+    }
+
+    pub fn entry_chars(&self) -> usize {
+        self.entry_chars.unwrap_or(500)
+    }
+}
+
+/// Spend controls
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct UsageConfig {
+    /// Refuse LLM calls once this many USD have been spent in the calendar month
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monthly_cap: Option<f64>,
+}
+
+/// Number of history commands sent when nothing else is configured
+pub const DEFAULT_HISTORY_COUNT: usize = 10;
+
+/// File name looked up from the working directory upwards for per-project overrides
+pub const PROJECT_CONFIG_FILE: &str = ".sorry.toml";
+
+/// Per-project overrides from `.sorry.toml`, layered over the global config
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub mood: Option<Mood>,
+    pub provider: Option<String>,
+    /// Model for the active provider
+    pub model: Option<String>,
+    pub history_count: Option<usize>,
+    /// Extra context appended to the system prompt, e.g. "This repo uses pnpm workspaces."
+    pub prompt_notes: Option<String>,
+    /// Where the file was found
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+/// Current config schema version, bumped whenever a migration is needed
+pub const CONFIG_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
+    /// Schema version; files without one predate versioning (0)
+    #[serde(default)]
+    pub version: u32,
     pub provider: Option<String>,
     pub mood: Option<Mood>,
+    #[serde(default)]
     pub providers: HashMap<String, ProviderConfig>,
+    /// Number of recent commands included as context (default 10)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_count: Option<usize>,
+    /// Directory of Markdown runbook snippets added to the system prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runbooks_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub log: LogConfig,
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    #[serde(default)]
+    pub usage: UsageConfig,
+    #[serde(default)]
+    pub context: ContextConfig,
+    #[serde(default, skip_serializing_if = "HooksConfig::is_empty")]
+    pub hooks: HooksConfig,
+    /// External commands that add context, see `ContextProviderConfig`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context_providers: Vec<ContextProviderConfig>,
+    /// Project overrides applied by `load_config`, never saved
+    #[serde(skip)]
+    pub project: Option<ProjectConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            version: CONFIG_VERSION,
+            provider: None,
+            mood: None,
+            providers: HashMap::new(),
+            history_count: None,
+            runbooks_dir: None,
+            network: NetworkConfig::default(),
+            cache: CacheConfig::default(),
+            log: LogConfig::default(),
+            budget: BudgetConfig::default(),
+            usage: UsageConfig::default(),
+            context: ContextConfig::default(),
+            hooks: HooksConfig::default(),
+            context_providers: Vec::new(),
+            project: None,
+        }
+    }
 }
 
 pub fn default_model(provider: &str) -> &'static str {
@@ -114,6 +490,20 @@ pub fn default_base_url(provider: &str) -> &'static str {
 }
 
 impl Config {
+    /// Full system prompt: base rules, mood personality and any project notes
+    pub fn system_prompt(&self) -> String {
+        let mut prompt = self.mood.unwrap_or_default().system_prompt();
+        if let Some(notes) = self.project.as_ref().and_then(|p| p.prompt_notes.as_deref()) {
+            prompt.push_str("\n\nPROJECT NOTES:\n");
+            prompt.push_str(notes.trim());
+        }
+        prompt
+    }
+
+    pub fn history_count(&self) -> usize {
+        self.history_count.unwrap_or(DEFAULT_HISTORY_COUNT)
+    }
+
     pub fn default_providers() -> HashMap<String, ProviderConfig> {
         let mut providers = HashMap::new();
         providers.insert(
@@ -122,6 +512,7 @@ impl Config {
                 api_key: String::new(),
                 base_url: default_base_url("openai").to_string(),
                 model: default_model("openai").to_string(),
+                ..Default::default()
             },
         );
         providers.insert(
@@ -130,6 +521,7 @@ impl Config {
                 api_key: String::new(),
                 base_url: default_base_url("groq").to_string(),
                 model: default_model("groq").to_string(),
+                ..Default::default()
             },
         );
         providers
@@ -140,29 +532,208 @@ impl Config {
 // Config file helpers
 // ============================================================================
 
-pub fn get_config_path() -> PathBuf {
-    let config_dir = dirs::config_dir()
+pub fn get_config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("sorry")
+}
+
+/// Where sorry keeps data it generates (log, usage ledger, fix library)
+pub fn get_data_dir() -> PathBuf {
+    dirs::data_dir()
         .unwrap_or_else(|| PathBuf::from("."))
-        .join("sorry");
-    config_dir.join("config.json")
+        .join("sorry")
 }
 
-pub fn load_config() -> Config {
+/// Expand a leading `~/` so paths in the config can be written like in a shell
+pub fn expand_home(path: &Path) -> PathBuf {
+    if let Ok(rest) = path.strip_prefix("~") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    path.to_path_buf()
+}
+
+pub fn get_config_path() -> PathBuf {
+    get_config_dir().join("config.toml")
+}
+
+/// Pre-TOML config location, migrated to `config.toml` on first load
+pub fn get_legacy_config_path() -> PathBuf {
+    get_config_dir().join("config.json")
+}
+
+/// Write a file readable only by the current user (0600 on unix)
+pub fn write_private_file(path: &Path, content: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    {
+        // `mode` only applies on creation; tighten files that already existed too
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(content.as_bytes())
+}
+
+/// Warn if a file holding secrets can be read by group or others
+#[cfg(unix)]
+fn warn_if_readable_by_others(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Ok(metadata) = fs::metadata(path) {
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            eprintln!(
+                "Warning: {} is readable by other users (mode {:o}). Run: chmod 600 {}",
+                path.display(),
+                mode,
+                path.display()
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn warn_if_readable_by_others(_path: &Path) {}
+
+/// Load the effective config: the global file with the nearest `.sorry.toml` layered on top.
+/// Precedence is command-line flags > project file > global file > defaults.
+/// Use `load_global_config` when the result is going to be saved.
+pub fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
+    let mut config = load_global_config()?;
+    if let Some(project) = find_project_config()? {
+        config.apply_project(project);
+    }
+    Ok(config)
+}
+
+impl Config {
+    fn apply_project(&mut self, project: ProjectConfig) {
+        if let Some(mood) = project.mood {
+            self.mood = Some(mood);
+        }
+        if let Some(provider) = &project.provider {
+            self.provider = Some(provider.clone());
+        }
+        if let Some(model) = &project.model {
+            if let Some(pc) = self.provider.as_ref().and_then(|p| self.providers.get_mut(p)) {
+                pc.model = model.clone();
+            }
+        }
+        if let Some(count) = project.history_count {
+            self.history_count = Some(count);
+        }
+        self.project = Some(project);
+    }
+}
+
+/// Walk up from the working directory to the nearest `.sorry.toml`
+fn find_project_config() -> Result<Option<ProjectConfig>, Box<dyn std::error::Error>> {
+    let Ok(cwd) = std::env::current_dir() else {
+        return Ok(None);
+    };
+
+    for dir in cwd.ancestors() {
+        let path = dir.join(PROJECT_CONFIG_FILE);
+        if !path.is_file() {
+            continue;
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut project: ProjectConfig = toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}:\n{}", path.display(), e))?;
+        project.path = path;
+        return Ok(Some(project));
+    }
+
+    Ok(None)
+}
+
+/// Load the global config file, migrating legacy or outdated files first.
+/// Parse errors are fatal so a typo never silently wipes the config.
+pub fn load_global_config() -> Result<Config, Box<dyn std::error::Error>> {
     let path = get_config_path();
     if path.exists() {
-        let content = fs::read_to_string(&path).unwrap_or_default();
-        serde_json::from_str(&content).unwrap_or_default()
-    } else {
-        Config::default()
+        warn_if_readable_by_others(&path);
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let config: Config = toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}:\n{}", path.display(), e))?;
+        return migrate_config(config, &path);
+    }
+
+    let legacy_path = get_legacy_config_path();
+    if legacy_path.exists() {
+        warn_if_readable_by_others(&legacy_path);
+        let content = fs::read_to_string(&legacy_path)
+            .map_err(|e| format!("Failed to read {}: {}", legacy_path.display(), e))?;
+        let config: Config = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {}", legacy_path.display(), e))?;
+        return migrate_config(config, &legacy_path);
     }
+
+    Ok(Config::default())
+}
+
+/// Bring an older config up to `CONFIG_VERSION`, keeping a backup of the original file
+fn migrate_config(mut config: Config, source: &Path) -> Result<Config, Box<dyn std::error::Error>> {
+    let is_legacy_json = source != get_config_path();
+    if config.version == CONFIG_VERSION && !is_legacy_json {
+        return Ok(config);
+    }
+    if config.version > CONFIG_VERSION {
+        return Err(format!(
+            "{} has version {}, but this sorry only understands up to version {}. Please upgrade sorry.",
+            source.display(),
+            config.version,
+            CONFIG_VERSION
+        )
+        .into());
+    }
+
+    // Version 0 -> 1: unversioned JSON becomes versioned TOML, no field changes
+    let from_version = config.version;
+    config.version = CONFIG_VERSION;
+
+    let mut backup = source.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", from_version));
+    let backup = PathBuf::from(backup);
+    // The original may hold plaintext keys, so the backup stays private too
+    let original = fs::read_to_string(source)?;
+    write_private_file(&backup, &original)
+        .map_err(|e| format!("Failed to back up {}: {}", source.display(), e))?;
+
+    save_config(&config)?;
+    if is_legacy_json {
+        fs::remove_file(source)?;
+    }
+
+    eprintln!(
+        "Migrated {} to {} (version {}). Backup saved to {}",
+        source.display(),
+        get_config_path().display(),
+        CONFIG_VERSION,
+        backup.display()
+    );
+    Ok(config)
 }
 
 pub fn save_config(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     let path = get_config_path();
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
-    }
-    let content = serde_json::to_string_pretty(config)?;
-    fs::write(&path, content)?;
+    let content = toml::to_string_pretty(config)?;
+    write_private_file(&path, &content)?;
     Ok(())
 }
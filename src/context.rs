@@ -0,0 +1,217 @@
+use regex::Regex;
+use std::env;
+use std::io::{self, Read};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crate::probe::run_with_timeout;
+
+// ============================================================================
+// Captured output (piped stdin, tmux scrollback)
+// ============================================================================
+
+/// Where captured output came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputSource {
+    Stdin,
+    Tmux,
+}
+
+/// Output captured alongside the question
+#[derive(Debug, Clone)]
+pub struct CapturedOutput {
+    pub source: InputSource,
+    pub text: String,
+}
+
+/// Most bytes kept from stdin; earlier output is discarded as more arrives
+const MAX_STDIN_BYTES: usize = 256 * 1024;
+
+/// Question asked when output is piped in without a message
+pub const DEFAULT_PIPED_PROMPT: &str = "What went wrong here and how do I fix it?";
+
+/// Lines from the end of captured output used for the error signature
+const SIGNATURE_LINES: usize = 10;
+
+/// Whether stdin is a pipe or a redirected file. Terminals, sockets and other
+/// inherited handles (editors, CI runners) are left alone so sorry never blocks on them.
+#[cfg(unix)]
+fn stdin_is_piped() -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    std::fs::metadata("/dev/stdin")
+        .map(|m| m.file_type().is_fifo() || m.file_type().is_file())
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn stdin_is_piped() -> bool {
+    use std::io::IsTerminal;
+    !io::stdin().is_terminal()
+}
+
+/// Read piped stdin (`cargo build 2>&1 | sorry why`), or `None` when nothing is piped in
+pub fn read_piped_input() -> io::Result<Option<CapturedOutput>> {
+    if !stdin_is_piped() {
+        return Ok(None);
+    }
+    let stdin = io::stdin();
+    let mut bytes = Vec::new();
+    let mut chunk = [0u8; 64 * 1024];
+    let mut reader = stdin.lock();
+    loop {
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..read]);
+        if bytes.len() > 2 * MAX_STDIN_BYTES {
+            bytes.drain(..bytes.len() - MAX_STDIN_BYTES);
+        }
+    }
+    if bytes.len() > MAX_STDIN_BYTES {
+        bytes.drain(..bytes.len() - MAX_STDIN_BYTES);
+    }
+    let text = String::from_utf8_lossy(&bytes).into_owned();
+    Ok((!text.trim().is_empty()).then_some(CapturedOutput {
+        source: InputSource::Stdin,
+        text,
+    }))
+}
+
+/// Longest `tmux capture-pane` may take
+const TMUX_TIMEOUT: Duration = Duration::from_secs(2);
+
+static ANSI_ESCAPE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(\x07|\x1b\\)|\x1b[@-_]")
+        .expect("valid ANSI escape pattern")
+});
+
+/// A shell prompt: a short prefix with `@`, `:` or `~` like `user@host:~/src$ `, or a
+/// bare `❯ `. A `%` right after a digit is progress output, never a prompt.
+static DEFAULT_PROMPT_MARKER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\S{0,40}[@:~].{0,40}?([^\d\s][$#%]|[❯➜»]) |^[❯➜»] ")
+        .expect("valid prompt pattern")
+});
+
+pub fn strip_ansi(text: &str) -> String {
+    ANSI_ESCAPE.replace_all(text, "").into_owned()
+}
+
+/// Keep what the last command printed: drop the line running sorry itself (and anything
+/// after it), then everything before the previous prompt.
+pub fn trim_to_last_command(text: &str, prompt_marker: &Regex) -> String {
+    let mut lines: Vec<&str> = text.lines().collect();
+    while lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+    if let Some(own) = lines
+        .iter()
+        .rposition(|l| prompt_marker.is_match(l) && l.contains("sorry"))
+    {
+        lines.truncate(own);
+    }
+    if let Some(previous) = lines.iter().rposition(|l| prompt_marker.is_match(l)) {
+        lines.drain(..previous);
+    }
+    lines.join("\n")
+}
+
+/// Scrollback of the current tmux pane, cleaned up, covering the last command and its output
+pub fn capture_tmux(
+    lines: usize,
+    prompt_pattern: Option<&str>,
+) -> Result<CapturedOutput, Box<dyn std::error::Error>> {
+    if env::var("TMUX").is_err() {
+        return Err("--from-tmux only works inside a tmux session.".into());
+    }
+    let start = format!("-{}", lines);
+    let mut args = vec!["capture-pane", "-p", "-J", "-S", start.as_str()];
+    let pane = env::var("TMUX_PANE").unwrap_or_default();
+    if !pane.is_empty() {
+        args.extend(["-t", pane.as_str()]);
+    }
+    let raw = run_with_timeout("tmux", &args, TMUX_TIMEOUT)
+        .ok_or("Failed to run 'tmux capture-pane'. Is tmux installed and the server running?")?;
+
+    let custom = prompt_pattern
+        .map(|p| Regex::new(p).map_err(|e| format!("Invalid context.prompt_pattern: {}", e)))
+        .transpose()?;
+    let marker = custom.as_ref().unwrap_or(&DEFAULT_PROMPT_MARKER);
+    let text = trim_to_last_command(&strip_ansi(&raw), marker);
+    if text.trim().is_empty() {
+        return Err("Nothing to capture from the tmux pane.".into());
+    }
+    Ok(CapturedOutput {
+        source: InputSource::Tmux,
+        text,
+    })
+}
+
+/// The end of the output, where errors usually are, for signatures and rule matching
+pub fn error_excerpt(output: &str) -> String {
+    let lines: Vec<&str> = output.lines().filter(|l| !l.trim().is_empty()).collect();
+    lines[lines.len().saturating_sub(SIGNATURE_LINES)..].join("\n")
+}
+
+/// Label captured output so the model can tell it apart from history
+pub fn format_captured_output(source: InputSource, output: &str) -> String {
+    if output.trim().is_empty() {
+        return String::new();
+    }
+    let label = match source {
+        InputSource::Stdin => "Here is the output I piped in:",
+        InputSource::Tmux => "Here is the end of my terminal (tmux pane), from the last command on:",
+    };
+    format!("{}\n```\n{}\n```\n\n", label, output.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_marker_matches_prompts() {
+        for line in [
+            "user@host:~/src$ cargo build",
+            "[user@host src]$ make",
+            "root@box:/# ls",
+            "~/src ❯ git push",
+            "❯ npm test",
+        ] {
+            assert!(DEFAULT_PROMPT_MARKER.is_match(line), "{}", line);
+        }
+    }
+
+    #[test]
+    fn default_marker_ignores_progress_output() {
+        for line in [
+            "100% |████████████| 12.3 MB",
+            "Downloading 45% done",
+            "Receiving objects:  45% (9/20)",
+            "warning: unused variable",
+        ] {
+            assert!(!DEFAULT_PROMPT_MARKER.is_match(line), "{}", line);
+        }
+    }
+
+    #[test]
+    fn trims_to_the_last_command() {
+        let scrollback = "user@host:~$ ls\n\
+                          a b\n\
+                          user@host:~$ cargo build\n\
+                          Compiling 45% (3/7)\n\
+                          error: linker not found\n\
+                          user@host:~$ sorry why\n\
+                          thinking...\n\n";
+        assert_eq!(
+            trim_to_last_command(scrollback, &DEFAULT_PROMPT_MARKER),
+            "user@host:~$ cargo build\nCompiling 45% (3/7)\nerror: linker not found"
+        );
+    }
+
+    #[test]
+    fn keeps_everything_without_a_prompt() {
+        let text = "line one\nline two";
+        assert_eq!(trim_to_last_command(text, &DEFAULT_PROMPT_MARKER), text);
+    }
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::ContextConfig;
+use crate::probe::first_line;
+
+// ============================================================================
+// Environment fingerprint (opt-in context)
+// ============================================================================
+
+/// Longest any single version probe may take
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Toolchains looked for, with the arguments that print their version
+const TOOLCHAINS: &[(&str, &str, &[&str])] = &[
+    ("Cargo", "cargo", &["--version"]),
+    ("Node", "node", &["--version"]),
+    ("Python", "python3", &["--version"]),
+    ("Docker", "docker", &["--version"]),
+    ("kubectl", "kubectl", &["version", "--client"]),
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedEnvironment {
+    /// Unix timestamp (seconds) when the fingerprint was taken
+    created_at: u64,
+    /// Hash of $SHELL and $PATH; a new shell or toolchain on PATH invalidates the cache
+    key: String,
+    description: String,
+}
+
+fn get_environment_cache_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("sorry")
+        .join("environment.json")
+}
+
+fn environment_key() -> String {
+    let mut hasher = Sha256::new();
+    for var in ["SHELL", "PATH"] {
+        hasher.update(env::var(var).unwrap_or_default().as_bytes());
+        hasher.update([0u8]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// PRETTY_NAME from /etc/os-release, falling back to the kernel or macOS version
+fn os_description() -> String {
+    let arch = env::consts::ARCH;
+    if let Ok(content) = fs::read_to_string("/etc/os-release") {
+        let pretty = content.lines().find_map(|line| {
+            line.strip_prefix("PRETTY_NAME=")
+                .map(|v| v.trim_matches('"').to_string())
+        });
+        if let Some(pretty) = pretty {
+            return format!("{} ({})", pretty, arch);
+        }
+    }
+    if env::consts::OS == "macos" {
+        if let Some(version) = first_line("sw_vers", &["-productVersion"], PROBE_TIMEOUT) {
+            return format!("macOS {} ({})", version, arch);
+        }
+    }
+    match first_line("uname", &["-sr"], PROBE_TIMEOUT) {
+        Some(uname) => format!("{} ({})", uname, arch),
+        None => format!("{} ({})", env::consts::OS, arch),
+    }
+}
+
+/// Probe the machine; version commands run in parallel so a slow one doesn't add up
+fn collect_environment() -> String {
+    let shell = env::var("SHELL").ok();
+
+    let (os, shell_version, git, toolchains) = thread::scope(|scope| {
+        let os = scope.spawn(os_description);
+        let shell_version = scope.spawn(|| {
+            shell
+                .as_deref()
+                .and_then(|shell| first_line(shell, &["--version"], PROBE_TIMEOUT))
+        });
+        let git = scope.spawn(|| first_line("git", &["--version"], PROBE_TIMEOUT));
+        let toolchains: Vec<_> = TOOLCHAINS
+            .iter()
+            .map(|(name, program, args)| {
+                scope.spawn(move || {
+                    first_line(program, args, PROBE_TIMEOUT).map(|v| format!("{}: {}", name, v))
+                })
+            })
+            .collect();
+        (
+            os.join().unwrap_or_default(),
+            shell_version.join().ok().flatten(),
+            git.join().ok().flatten(),
+            toolchains
+                .into_iter()
+                .filter_map(|t| t.join().ok().flatten())
+                .collect::<Vec<_>>(),
+        )
+    });
+
+    let mut lines = vec![format!("OS: {}", os)];
+    match (shell, shell_version) {
+        (_, Some(version)) => lines.push(format!("Shell: {}", version)),
+        (Some(shell), None) => lines.push(format!("Shell: {}", shell)),
+        (None, None) => {}
+    }
+    if let Some(git) = git {
+        lines.push(format!("Git: {}", git));
+    }
+    if toolchains.is_empty() {
+        lines.push("Toolchains: none of cargo, node, python, docker, kubectl found".to_string());
+    } else {
+        lines.extend(toolchains);
+    }
+    lines.join("\n")
+}
+
+/// Describe the user's environment, reusing a cached description while it is fresh
+pub fn environment_description(config: &ContextConfig) -> String {
+    let path = get_environment_cache_path();
+    let key = environment_key();
+
+    let cached = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<CachedEnvironment>(&content).ok());
+    if let Some(cached) = cached {
+        let fresh = now_secs().saturating_sub(cached.created_at) <= config.environment_ttl().as_secs();
+        if fresh && cached.key == key {
+            return cached.description;
+        }
+    }
+
+    let description = collect_environment();
+    let entry = CachedEnvironment {
+        created_at: now_secs(),
+        key,
+        description: description.clone(),
+    };
+    // Failing to cache only means probing again next time
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if let Ok(content) = serde_json::to_string(&entry) {
+        let _ = fs::write(&path, content);
+    }
+    description
+}
+
+/// System prompt section describing the environment
+pub fn format_environment(description: &str) -> String {
+    format!("\n\nUSER ENVIRONMENT:\n{}", description)
+}
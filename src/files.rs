@@ -0,0 +1,335 @@
+use regex::Regex;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::budget::{estimate_tokens, fit_section, Keep};
+use crate::redact::redact;
+
+// ============================================================================
+// Files attached to the prompt
+// ============================================================================
+
+/// Lines shown on each side of a referenced line
+const WINDOW_LINES: usize = 10;
+
+/// Most references picked up automatically from output
+const MAX_DETECTED_REFS: usize = 5;
+
+/// Files larger than this are never read
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Credential files that don't start with a dot, e.g. SSH keys copied into a repo
+const DENIED_FILE_NAMES: &[&str] = &[
+    "id_rsa",
+    "id_dsa",
+    "id_ecdsa",
+    "id_ed25519",
+    "credentials",
+    "credentials.json",
+];
+
+/// Extensions of key and certificate bundles
+const DENIED_EXTENSIONS: &[&str] = &["pem", "key", "p12", "pfx"];
+
+/// A file, or a range of its lines (1-based, inclusive), to show the model
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileRef {
+    pub path: PathBuf,
+    pub lines: Option<(usize, usize)>,
+}
+
+impl FileRef {
+    fn around(path: PathBuf, line: usize) -> FileRef {
+        FileRef {
+            path,
+            lines: Some((
+                line.saturating_sub(WINDOW_LINES).max(1),
+                line + WINDOW_LINES,
+            )),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self.lines {
+            Some((start, end)) => format!("{}:{}-{}", self.path.display(), start, end),
+            None => self.path.display().to_string(),
+        }
+    }
+}
+
+/// Parse `--file path`, `path:42` (a window around line 42) or `path:40-60`
+pub fn parse_file_arg(arg: &str) -> Result<FileRef, Box<dyn std::error::Error>> {
+    let (path, range) = match arg.rsplit_once(':') {
+        Some((path, range))
+            if !range.is_empty() && range.chars().all(|c| c.is_ascii_digit() || c == '-') =>
+        {
+            (path, Some(range))
+        }
+        _ => (arg, None),
+    };
+
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("--file: {} is not a file", path.display()).into());
+    }
+
+    let invalid = || {
+        format!(
+            "--file: invalid line range '{}' in {}",
+            range.unwrap_or_default(),
+            arg
+        )
+    };
+    match range {
+        None => Ok(FileRef { path, lines: None }),
+        Some(range) => match range.split_once('-') {
+            Some((start, end)) => {
+                let start: usize = start.parse().map_err(|_| invalid())?;
+                let end: usize = end.parse().map_err(|_| invalid())?;
+                if start == 0 || end < start {
+                    return Err(invalid().into());
+                }
+                Ok(FileRef {
+                    path,
+                    lines: Some((start, end)),
+                })
+            }
+            None => Ok(FileRef::around(path, range.parse().map_err(|_| invalid())?)),
+        },
+    }
+}
+
+/// Files may only be read below the git repository sorry runs in
+fn workspace_root(cwd: &Path) -> Result<PathBuf, String> {
+    cwd.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+        .ok_or_else(|| "not in a git repository; files can only be read inside one".to_string())
+}
+
+/// Dotfiles and dot-directories (`.env`, `.ssh/`, `.aws/`, `.git/`, ...) and files that
+/// usually hold credentials are never shown to the model, even inside the repository
+pub fn is_denied(name: &str) -> bool {
+    let extension = Path::new(name).extension().and_then(|e| e.to_str());
+    name.starts_with('.')
+        || DENIED_FILE_NAMES.contains(&name)
+        || extension.is_some_and(|e| DENIED_EXTENSIONS.contains(&e))
+}
+
+/// Resolve a path against `cwd`, refusing anything outside the repository or on the denylist
+fn resolve_path_in(cwd: &Path, path: &str) -> Result<PathBuf, String> {
+    let root = workspace_root(cwd)?
+        .canonicalize()
+        .map_err(|e| e.to_string())?;
+    let resolved = cwd
+        .join(path)
+        .canonicalize()
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let Ok(inside) = resolved.strip_prefix(&root) else {
+        return Err(format!("{} is outside the repository, not allowed", path));
+    };
+    if inside
+        .components()
+        .any(|c| is_denied(&c.as_os_str().to_string_lossy()))
+    {
+        return Err(format!("{} may hold secrets, not allowed", path));
+    }
+    Ok(resolved)
+}
+
+/// Resolve a path the model asked for or the output mentions, from the current directory
+pub fn resolve_path(path: &str) -> Result<PathBuf, String> {
+    let cwd = env::current_dir().map_err(|e| e.to_string())?;
+    resolve_path_in(&cwd, path)
+}
+
+static REFERENCE_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        // src/main.rs:42, ./app.ts:10:5, --> lib/x.go:7
+        r"([A-Za-z0-9_./\-]+\.[A-Za-z0-9]+):(\d+)",
+        // Python tracebacks: File "app/views.py", line 42
+        r#"File "([^"]+)", line (\d+)"#,
+    ]
+    .iter()
+    .map(|p| Regex::new(p).expect("valid file reference pattern"))
+    .collect()
+});
+
+/// Find `path:line` references to existing files of the repository in error output
+pub fn detect_file_refs(text: &str) -> Vec<FileRef> {
+    let mut refs: Vec<FileRef> = Vec::new();
+    for pattern in REFERENCE_PATTERNS.iter() {
+        for captures in pattern.captures_iter(text) {
+            let path = PathBuf::from(&captures[1]);
+            let Ok(line) = captures[2].parse::<usize>() else {
+                continue;
+            };
+            // Only files in the repository, and never ones that may hold secrets
+            let allowed = resolve_path(&captures[1]).is_ok_and(|resolved| resolved.is_file());
+            if line == 0 || !allowed {
+                continue;
+            }
+            let file_ref = FileRef::around(path, line);
+            if !refs.contains(&file_ref) {
+                refs.push(file_ref);
+            }
+            if refs.len() >= MAX_DETECTED_REFS {
+                return refs;
+            }
+        }
+    }
+    refs
+}
+
+/// Merge windows on the same file that overlap, so lines aren't sent twice
+pub fn merge_file_refs(refs: Vec<FileRef>) -> Vec<FileRef> {
+    let mut merged: Vec<FileRef> = Vec::new();
+    for file_ref in refs {
+        let overlapping = merged.iter_mut().find(|m| {
+            m.path == file_ref.path
+                && match (m.lines, file_ref.lines) {
+                    (Some((a1, a2)), Some((b1, b2))) => a1 <= b2 + 1 && b1 <= a2 + 1,
+                    _ => true,
+                }
+        });
+        match overlapping {
+            Some(existing) => {
+                existing.lines = match (existing.lines, file_ref.lines) {
+                    (Some((a1, a2)), Some((b1, b2))) => Some((a1.min(b1), a2.max(b2))),
+                    _ => None,
+                }
+            }
+            None => merged.push(file_ref),
+        }
+    }
+    merged
+}
+
+/// Numbered lines of a file, or `None` if it can't be read as text
+pub fn read_excerpt(file_ref: &FileRef) -> Option<String> {
+    if fs::metadata(&file_ref.path).ok()?.len() > MAX_FILE_BYTES {
+        return None;
+    }
+    let content = fs::read_to_string(&file_ref.path).ok()?;
+    let (start, end) = file_ref.lines.unwrap_or((1, usize::MAX));
+    let lines: Vec<String> = content
+        .lines()
+        .enumerate()
+        .skip(start - 1)
+        .take(end.saturating_sub(start) + 1)
+        .map(|(i, line)| format!("{:>5} | {}", i + 1, line))
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+fn display_path(path: &Path) -> String {
+    path.strip_prefix("./")
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// Labelled, redacted excerpts of each file, cut to fit `max_tokens` in total
+pub fn format_file_excerpts(refs: &[FileRef], max_tokens: usize) -> String {
+    let mut out = String::new();
+    for file_ref in refs {
+        let Some(excerpt) = read_excerpt(file_ref) else {
+            eprintln!(
+                "Warning: could not read {}, not attaching it",
+                file_ref.path.display()
+            );
+            continue;
+        };
+        let label = match file_ref.lines {
+            Some((start, end)) => {
+                format!("{} (lines {}-{})", display_path(&file_ref.path), start, end)
+            }
+            None => display_path(&file_ref.path),
+        };
+        let header = format!("Here is {}:\n```\n", label);
+        let room = max_tokens.saturating_sub(estimate_tokens(&out) + estimate_tokens(&header) + 2);
+        if room == 0 {
+            break;
+        }
+        out.push_str(&header);
+        out.push_str(&fit_section(&redact(&excerpt), room, Keep::Start));
+        out.push_str("\n```\n\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A throwaway repository with a source file, a secret and a sibling outside it
+    fn sandbox(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("sorry-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for sub in ["repo/.git", "repo/src", "repo/.aws", "outside"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        for file in [
+            "repo/src/main.rs",
+            "repo/.aws/credentials",
+            "repo/.env",
+            "repo/deploy.pem",
+            "outside/notes.txt",
+        ] {
+            fs::write(dir.join(file), "x").unwrap();
+        }
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn resolves_paths_inside_the_repository() {
+        let dir = sandbox("inside");
+        let src = dir.join("repo/src");
+        assert_eq!(
+            resolve_path_in(&src, "main.rs").unwrap(),
+            src.join("main.rs")
+        );
+        assert_eq!(resolve_path_in(&src, "..").unwrap(), dir.join("repo"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_paths_outside_the_repository() {
+        let dir = sandbox("outside");
+        let repo = dir.join("repo");
+        assert!(resolve_path_in(&repo, "../outside/notes.txt").is_err());
+        assert!(resolve_path_in(&repo, "/etc/passwd").is_err());
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.join("outside"), repo.join("link")).unwrap();
+            assert!(resolve_path_in(&repo, "link/notes.txt").is_err());
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_dotfiles_and_credentials() {
+        let dir = sandbox("denied");
+        let repo = dir.join("repo");
+        for path in [
+            ".aws/credentials",
+            ".env",
+            "deploy.pem",
+            ".git",
+            "src/../.env",
+        ] {
+            assert!(resolve_path_in(&repo, path).is_err(), "{}", path);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_everything_outside_a_git_repository() {
+        let dir = sandbox("norepo");
+        let outside = dir.join("outside");
+        assert!(resolve_path_in(&outside, "notes.txt").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
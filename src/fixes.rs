@@ -0,0 +1,281 @@
+use chrono::Local;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::config::{get_data_dir, write_private_file};
+use crate::journal::read_log;
+use crate::redact::redact;
+
+// ============================================================================
+// Personal fix library
+// ============================================================================
+
+/// Minimum Jaccard similarity between signatures to reuse a fix
+const SIMILARITY_THRESHOLD: f64 = 0.7;
+
+/// A rated answer tied to the error signature it fixed
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Fix {
+    pub signature: String,
+    pub question: String,
+    pub response: String,
+    /// Net rating: +1 per 👍, -1 per 👎
+    pub score: i32,
+    /// Unix timestamp (seconds) of the last rating
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rating {
+    Good,
+    Bad,
+}
+
+impl Rating {
+    pub fn parse(value: &str) -> Option<Rating> {
+        match value.to_lowercase().as_str() {
+            "good" | "up" | "+" | "+1" | "👍" => Some(Rating::Good),
+            "bad" | "down" | "-" | "-1" | "👎" => Some(Rating::Bad),
+            _ => None,
+        }
+    }
+}
+
+static VOLATILE_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        // Commit hashes, object ids
+        r"\b[0-9a-f]{7,40}\b",
+        // Numbers (line numbers, pids, ports, counts)
+        r"\d+",
+        // Quoted strings (branch names, messages)
+        r#""[^"]*"|'[^']*'"#,
+    ]
+    .iter()
+    .map(|p| Regex::new(p).expect("valid signature pattern"))
+    .collect()
+});
+
+/// Normalise context into a signature that survives incidental differences
+/// (hashes, numbers, quoted names) between two occurrences of the same error.
+/// Empty without error output: the same command can fail (or not) in many ways.
+/// Secrets are redacted first, as signatures are logged and exported.
+pub fn error_signature(last_command: &str, error_text: &str) -> String {
+    if error_text.trim().is_empty() {
+        return String::new();
+    }
+    let mut signature = format!(
+        "{}\n{}",
+        redact(last_command.trim()),
+        redact(error_text.trim())
+    )
+    .to_lowercase();
+    for pattern in VOLATILE_PATTERNS.iter() {
+        signature = pattern.replace_all(&signature, "#").into_owned();
+    }
+    signature.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn similarity(a: &str, b: &str) -> f64 {
+    let a: HashSet<&str> = a.split_whitespace().collect();
+    let b: HashSet<&str> = b.split_whitespace().collect();
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+}
+
+pub fn get_fixes_path() -> PathBuf {
+    get_data_dir().join("fixes.json")
+}
+
+pub fn load_fixes() -> Result<Vec<Fix>, Box<dyn std::error::Error>> {
+    let path = get_fixes_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?)
+}
+
+fn save_fixes(fixes: &[Fix]) -> Result<(), Box<dyn std::error::Error>> {
+    write_private_file(&get_fixes_path(), &serde_json::to_string_pretty(fixes)?)?;
+    Ok(())
+}
+
+/// Best positively rated fix whose signature is similar enough
+pub fn find_fix(fixes: &[Fix], signature: &str) -> Option<Fix> {
+    if signature.is_empty() {
+        return None;
+    }
+    fixes
+        .iter()
+        .filter(|f| f.score > 0)
+        .map(|f| (similarity(&f.signature, signature), f))
+        .filter(|(sim, _)| *sim >= SIMILARITY_THRESHOLD)
+        .max_by(|(sa, a), (sb, b)| sa.total_cmp(sb).then(a.score.cmp(&b.score)))
+        .map(|(_, f)| f.clone())
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Rate a logged answer (the latest one unless `id` is given)
+pub fn rate_answer(rating: Rating, id: Option<u64>) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = read_log()?;
+    let entry = match id {
+        Some(id) => entries.iter().find(|e| e.id == id),
+        None => entries.last(),
+    }
+    .ok_or("No logged answer to rate. Rating needs the log (log.enabled = true).")?;
+
+    if entry.signature.is_empty() {
+        return Err(format!(
+            "Answer #{} had no error output (piped in or from --from-tmux) to tie a fix to.",
+            entry.id
+        )
+        .into());
+    }
+
+    let mut fixes = load_fixes()?;
+    let existing = fixes
+        .iter_mut()
+        .find(|f| f.signature == entry.signature && f.response == entry.response);
+
+    let now = Local::now().timestamp();
+    match (existing, rating) {
+        (Some(fix), Rating::Good) => {
+            fix.score += 1;
+            fix.updated_at = now;
+        }
+        (Some(fix), Rating::Bad) => {
+            fix.score -= 1;
+            fix.updated_at = now;
+        }
+        (None, Rating::Good) => fixes.push(Fix {
+            signature: entry.signature.clone(),
+            question: entry.question.clone(),
+            response: entry.response.clone(),
+            score: 1,
+            updated_at: now,
+        }),
+        (None, Rating::Bad) => {}
+    }
+    fixes.retain(|f| f.score > 0);
+    save_fixes(&fixes)?;
+
+    match rating {
+        Rating::Good => println!("👍 Saved answer #{} to your fix library", entry.id),
+        Rating::Bad => println!("👎 Noted, answer #{} won't be offered again", entry.id),
+    }
+    Ok(())
+}
+
+/// Write the fix library as JSON to a file, or stdout
+pub fn export_fixes(output: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let fixes = load_fixes()?;
+    let content = serde_json::to_string_pretty(&fixes)?;
+    match output {
+        Some(path) => {
+            fs::write(path, content + "\n")?;
+            eprintln!("✓ Exported {} fixes to {}", fixes.len(), path.display());
+        }
+        None => println!("{}", content),
+    }
+    Ok(())
+}
+
+/// Merge fixes exported by a teammate into the library
+pub fn import_fixes(input: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(input)?;
+    let incoming: Vec<Fix> = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse {}: {}", input.display(), e))?;
+
+    let mut fixes = load_fixes()?;
+    let mut added = 0;
+    for fix in incoming.into_iter().filter(|f| f.score > 0) {
+        let duplicate = fixes
+            .iter()
+            .any(|f| f.signature == fix.signature && f.response == fix.response);
+        if !duplicate {
+            fixes.push(fix);
+            added += 1;
+        }
+    }
+    save_fixes(&fixes)?;
+
+    println!("✓ Imported {} new fixes", added);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fix(signature: &str, score: i32) -> Fix {
+        Fix {
+            signature: signature.to_string(),
+            question: "why".to_string(),
+            response: "git pull --rebase".to_string(),
+            score,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn signature_needs_error_output() {
+        assert_eq!(error_signature("git push", ""), "");
+        assert_eq!(error_signature("git push", "  \n"), "");
+    }
+
+    #[test]
+    fn signature_ignores_volatile_details() {
+        let a = error_signature(
+            "git push",
+            "! [rejected] main -> main (non-fast-forward)\nerror: failed to push some refs to 'git@github.com:me/a.git'",
+        );
+        let b = error_signature(
+            "git push",
+            "! [rejected] main -> main (non-fast-forward)\nerror: failed to push some refs to 'git@github.com:me/b.git'",
+        );
+        assert_eq!(a, b);
+        assert_eq!(
+            error_signature("cargo run", "panicked at src/main.rs:42:5"),
+            error_signature("cargo run", "panicked at src/main.rs:7:13")
+        );
+    }
+
+    #[test]
+    fn signature_has_no_secrets() {
+        let signature = error_signature(
+            "DEPLOY_PASSWORD=hunter2supersecret git push",
+            "DEPLOY_PASSWORD=hunter2supersecret\n! [rejected] main -> main (non-fast-forward)",
+        );
+        assert!(!signature.contains("hunter"), "{}", signature);
+        assert!(!signature.contains("supersecret"), "{}", signature);
+    }
+
+    #[test]
+    fn finds_similar_fixes_only() {
+        let saved = error_signature("git push", "! [rejected] main -> main (non-fast-forward)");
+        let fixes = vec![fix(&saved, 1)];
+
+        let again = error_signature("git push", "! [rejected] main -> main (non-fast-forward)");
+        assert!(find_fix(&fixes, &again).is_some());
+
+        let other = error_signature("npm install", "npm ERR! code ERESOLVE");
+        assert!(find_fix(&fixes, &other).is_none());
+        assert!(find_fix(&fixes, "").is_none());
+    }
+
+    #[test]
+    fn skips_down_rated_fixes() {
+        let saved = error_signature("git push", "! [rejected] (non-fast-forward)");
+        assert!(find_fix(&[fix(&saved, 0)], &saved).is_none());
+    }
+}
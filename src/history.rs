@@ -1,7 +1,50 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
 
+use crate::config::{get_data_dir, write_private_file};
+
+/// The command log is trimmed once it grows past this size
+const COMMAND_LOG_MAX_BYTES: u64 = 256 * 1024;
+
+/// Entries kept when the command log is trimmed
+const COMMAND_LOG_KEEP: usize = 1000;
+
+/// A command from history, with what the shell hooks recorded about it when available
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub command: String,
+    /// Directory the command ran in
+    pub cwd: Option<String>,
+    pub exit_status: Option<i32>,
+}
+
+impl HistoryEntry {
+    fn plain(command: String) -> HistoryEntry {
+        HistoryEntry {
+            command,
+            cwd: None,
+            exit_status: None,
+        }
+    }
+}
+
+/// One line of the command log written by the shell hooks
+#[derive(Debug, Serialize, Deserialize)]
+struct LoggedCommand {
+    /// Unix timestamp (seconds)
+    timestamp: i64,
+    /// Shell process id, so concurrent terminals don't mix
+    #[serde(default)]
+    session: Option<String>,
+    cwd: String,
+    command: String,
+    exit_status: Option<i32>,
+}
+
 /// Get the path to the shell history file
 fn get_history_path() -> Option<PathBuf> {
     // Check HISTFILE env var first (works for most shells)
@@ -40,8 +83,8 @@ fn get_history_path() -> Option<PathBuf> {
 
     // Also try expanding ~ in HISTFILE if it wasn't found
     if let Ok(histfile) = env::var("HISTFILE") {
-        if histfile.starts_with("~/") {
-            let expanded = home.join(&histfile[2..]);
+        if let Some(rest) = histfile.strip_prefix("~/") {
+            let expanded = home.join(rest);
             candidates.insert(0, expanded);
         }
     }
@@ -144,12 +187,12 @@ pub fn get_last_commands(count: usize) -> Vec<String> {
         // Bash - simpler, one command per line
         commands = content
             .lines()
-            .filter_map(|line| parse_bash_line(line))
+            .filter_map(parse_bash_line)
             .collect();
     }
 
     // Filter out sorry commands to avoid recursive context
-    commands.retain(|cmd| !cmd.trim().starts_with("sorry"));
+    commands.retain(|cmd| !is_sorry_command(cmd));
 
     // Get last N commands
     let start = commands.len().saturating_sub(count);
@@ -162,19 +205,139 @@ pub fn parse_commands_from_string(commands_str: &str) -> Vec<String> {
         .lines()
         .map(|line| line.trim().to_string())
         .filter(|line| !line.is_empty())
-        .filter(|cmd| !cmd.trim().starts_with("sorry"))
+        .filter(|cmd| !is_sorry_command(cmd))
         .collect()
 }
 
+/// Whether a command is a previous `sorry` call, left out to avoid recursive context
+fn is_sorry_command(command: &str) -> bool {
+    command.trim().starts_with("sorry")
+}
+
+pub fn get_command_log_path() -> PathBuf {
+    get_data_dir().join("commands.jsonl")
+}
+
+/// Append a finished command to the command log (called by the shell hooks)
+pub fn record_command(
+    session: Option<&str>,
+    cwd: &str,
+    command: &str,
+    exit_status: Option<i32>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let command = command.trim();
+    if command.is_empty() || is_sorry_command(command) {
+        return Ok(());
+    }
+    let entry = LoggedCommand {
+        timestamp: Local::now().timestamp(),
+        session: session.map(str::to_string),
+        cwd: cwd.to_string(),
+        command: command.to_string(),
+        exit_status,
+    };
+
+    let path = get_command_log_path();
+    if !path.exists() {
+        write_private_file(&path, "")?;
+    }
+    let mut file = fs::OpenOptions::new().read(true).append(true).open(&path)?;
+    // Every shell appends to the same log; holding the lock keeps a rotation
+    // from dropping lines another shell writes in the meantime
+    file.lock()?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+
+    // Rotate: keep only the newest entries once the file gets big
+    if file.metadata()?.len() > COMMAND_LOG_MAX_BYTES {
+        let mut content = String::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_string(&mut content)?;
+        let lines: Vec<&str> = content.lines().collect();
+        let keep = &lines[lines.len().saturating_sub(COMMAND_LOG_KEEP)..];
+        file.set_len(0)?;
+        file.write_all((keep.join("\n") + "\n").as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Last `count` commands the hooks recorded for one shell session
+fn read_command_log(session: &str, count: usize) -> Vec<HistoryEntry> {
+    let Ok(content) = fs::read_to_string(get_command_log_path()) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<HistoryEntry> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<LoggedCommand>(line).ok())
+        .filter(|e| e.session.as_deref() == Some(session))
+        .filter(|e| !is_sorry_command(&e.command))
+        .map(|e| HistoryEntry {
+            command: e.command,
+            cwd: Some(e.cwd),
+            exit_status: e.exit_status,
+        })
+        .collect();
+    let start = entries.len().saturating_sub(count);
+    entries.drain(..start);
+    entries
+}
+
+/// Recent commands from the best source available: the command log for this shell
+/// session, the commands passed in by the shell function, and finally the shell's
+/// history file. Without a session id the log is not used, as it mixes every shell.
+pub fn load_history(last_commands: Option<&str>, count: usize) -> Vec<HistoryEntry> {
+    let session = env::var("SORRY_SESSION").ok().filter(|s| !s.is_empty());
+    if let Some(session) = session.as_deref() {
+        let entries = read_command_log(session, count);
+        if !entries.is_empty() {
+            return entries;
+        }
+    }
+    if let Some(commands) = last_commands {
+        return parse_commands_from_string(commands)
+            .into_iter()
+            .map(HistoryEntry::plain)
+            .collect();
+    }
+    get_last_commands(count)
+        .into_iter()
+        .map(HistoryEntry::plain)
+        .collect()
+}
+
+/// Shorten the home directory to `~`, so the model sees less of the machine
+fn display_cwd(cwd: &str) -> String {
+    match dirs::home_dir().and_then(|home| {
+        PathBuf::from(cwd)
+            .strip_prefix(&home)
+            .ok()
+            .map(|rest| rest.display().to_string())
+    }) {
+        Some(rest) if rest.is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest),
+        None => cwd.to_string(),
+    }
+}
+
 /// Format commands for inclusion in prompt
-pub fn format_history_context(commands: &[String]) -> String {
+pub fn format_history_context(commands: &[HistoryEntry]) -> String {
     if commands.is_empty() {
         return String::new();
     }
 
-    let mut context = String::from("Here are my last terminal commands:\n```\n");
-    for (i, cmd) in commands.iter().enumerate() {
-        context.push_str(&format!("{}. {}\n", i + 1, cmd));
+    let mut context = if commands.iter().any(|c| c.cwd.is_some()) {
+        String::from("Here are my last terminal commands (directory $ command [exit status]):\n```\n")
+    } else {
+        String::from("Here are my last terminal commands:\n```\n")
+    };
+    for (i, entry) in commands.iter().enumerate() {
+        let mut line = match &entry.cwd {
+            Some(cwd) => format!("{} $ {}", display_cwd(cwd), entry.command),
+            None => entry.command.clone(),
+        };
+        if let Some(status) = entry.exit_status {
+            line.push_str(&format!(" [exit {}]", status));
+        }
+        context.push_str(&format!("{}. {}\n", i + 1, line));
     }
     context.push_str("```\n\n");
     context
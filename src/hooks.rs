@@ -0,0 +1,118 @@
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::config::HookConfig;
+use crate::probe::run_with_input;
+
+// ============================================================================
+// Pre-request and post-response hooks
+// ============================================================================
+//
+// Each hook is a shell command that gets one JSON object on stdin:
+//
+//   pre_request:   {"hook": "pre_request", "provider": "...", "request": {...}}
+//   post_response: {"hook": "post_response", "provider": "...", "model": "...",
+//                   "request": {...}, "response": {"text": "...", "usage": {...}}}
+//
+// and may print a JSON object back with a replacement `request` or `response`.
+// Printing nothing (or leaving the key out) keeps the value as it was, so a hook
+// that only logs can ignore stdout entirely. Hooks run in the order configured,
+// each seeing what the previous one returned.
+
+/// Default time limit for a hook
+const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 5;
+
+/// Run one hook and parse what it printed, `None` if it printed nothing
+fn run_hook(hook: &HookConfig, payload: &Value) -> Result<Option<Value>, String> {
+    let timeout = Duration::from_secs(hook.timeout_secs.unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS));
+    let output = run_with_input("sh", &["-c", &hook.cmd], &payload.to_string(), timeout)?;
+    if output.trim().is_empty() {
+        return Ok(None);
+    }
+    let value: Value =
+        serde_json::from_str(&output).map_err(|e| format!("printed invalid JSON: {}", e))?;
+    if !value.is_object() {
+        return Err("printed JSON that is not an object".to_string());
+    }
+    Ok(Some(value))
+}
+
+/// Report a failed hook; only required hooks stop the request
+fn hook_failed(
+    hook: &HookConfig,
+    event: &str,
+    reason: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if hook.required {
+        return Err(format!("Required {} hook '{}' failed: {}", event, hook.name, reason).into());
+    }
+    eprintln!(
+        "Warning: {} hook '{}' failed, ignoring it: {}",
+        event, hook.name, reason
+    );
+    Ok(())
+}
+
+/// A request must still look like a chat completion request after a hook
+fn check_request(request: &Value) -> Result<(), String> {
+    if !request["model"].is_string() || !request["messages"].is_array() {
+        return Err("returned a request without `model` and `messages`".to_string());
+    }
+    Ok(())
+}
+
+/// Pass the outgoing request body through the `pre_request` hooks
+pub fn run_pre_request_hooks(
+    hooks: &[HookConfig],
+    provider: &str,
+    mut request: Value,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    for hook in hooks {
+        let payload = json!({ "hook": "pre_request", "provider": provider, "request": request });
+        let result = run_hook(hook, &payload).and_then(|output| {
+            match output.and_then(|mut o| o.get_mut("request").map(Value::take)) {
+                Some(replacement) => check_request(&replacement).map(|_| Some(replacement)),
+                None => Ok(None),
+            }
+        });
+        match result {
+            Ok(Some(replacement)) => request = replacement,
+            Ok(None) => {}
+            Err(reason) => hook_failed(hook, "pre_request", reason)?,
+        }
+    }
+    Ok(request)
+}
+
+/// Pass a fresh answer through the `post_response` hooks and return its final text
+pub fn run_post_response_hooks(
+    hooks: &[HookConfig],
+    provider: &str,
+    model: &str,
+    request: &Value,
+    response: Value,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut response = response;
+    for hook in hooks {
+        let payload = json!({
+            "hook": "post_response",
+            "provider": provider,
+            "model": model,
+            "request": request,
+            "response": response,
+        });
+        let result = run_hook(hook, &payload).and_then(|output| {
+            match output.and_then(|mut o| o.get_mut("response").map(Value::take)) {
+                Some(replacement) if replacement["text"].is_string() => Ok(Some(replacement)),
+                Some(_) => Err("returned a response without `text`".to_string()),
+                None => Ok(None),
+            }
+        });
+        match result {
+            Ok(Some(replacement)) => response = replacement,
+            Ok(None) => {}
+            Err(reason) => hook_failed(hook, "post_response", reason)?,
+        }
+    }
+    Ok(response["text"].as_str().unwrap_or_default().to_string())
+}
@@ -0,0 +1,278 @@
+use regex::Regex;
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crate::budget::{truncate_chars, Keep};
+use crate::files::{is_denied, read_excerpt, resolve_path, FileRef};
+use crate::probe::run_with_input;
+use crate::redact::redact;
+
+// ============================================================================
+// Read-only tools for `--investigate`
+// ============================================================================
+
+/// Most rounds of tool calls before the model must answer
+pub const MAX_INVESTIGATE_STEPS: usize = 6;
+
+/// Longest a single tool may run
+const TOOL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Output beyond this is cut before it goes back to the model
+const MAX_TOOL_OUTPUT_CHARS: usize = 6000;
+
+/// Most entries shown by list_dir
+const MAX_DIR_ENTRIES: usize = 200;
+
+/// Most commits shown by git_log
+const MAX_LOG_COMMITS: u64 = 30;
+
+static PROGRAM_NAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Za-z0-9._+-]+$").expect("valid program name pattern"));
+
+/// Appended to the system prompt when `--investigate` is used
+pub fn investigate_prompt() -> String {
+    format!(
+        "\n\nINVESTIGATION:\nYou can call read-only tools to look at the user's machine: git status, git log, \
+         files and directories in the current repository, and where programs are on PATH. \
+         Use them when the context above isn't enough to be sure what went wrong, at most {} rounds, \
+         then give your final fix following the rules above.",
+        MAX_INVESTIGATE_STEPS
+    )
+}
+
+/// Function definitions offered to the model, in the chat completions `tools` format
+pub fn tool_definitions() -> Value {
+    let tool = |name: &str, description: &str, parameters: Value| {
+        json!({
+            "type": "function",
+            "function": { "name": name, "description": description, "parameters": parameters }
+        })
+    };
+    json!([
+        tool(
+            "git_status",
+            "Show the working tree status and current branch (git status --short --branch).",
+            json!({ "type": "object", "properties": {} })
+        ),
+        tool(
+            "git_log",
+            "Show recent commits, one per line, optionally only those touching a path.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "count": { "type": "integer", "description": "Number of commits (default 10, max 30)" },
+                    "path": { "type": "string", "description": "Only commits touching this path" }
+                }
+            })
+        ),
+        tool(
+            "read_file",
+            "Read a text file in the current repository, optionally only some lines.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "start_line": { "type": "integer" },
+                    "end_line": { "type": "integer" }
+                },
+                "required": ["path"]
+            })
+        ),
+        tool(
+            "list_dir",
+            "List a directory in the current repository.",
+            json!({
+                "type": "object",
+                "properties": { "path": { "type": "string", "description": "Default: the current directory" } }
+            })
+        ),
+        tool(
+            "which",
+            "Find where a program is on PATH.",
+            json!({
+                "type": "object",
+                "properties": { "program": { "type": "string" } },
+                "required": ["program"]
+            })
+        ),
+    ])
+}
+
+/// A tool call the model asked for, after checking it against the allowlist
+#[derive(Debug)]
+pub struct ToolRun {
+    /// What is shown to the user, e.g. `git log -n 10`
+    pub display: String,
+    /// What goes back to the model
+    pub output: String,
+}
+
+fn git(args: &[&str]) -> Result<String, String> {
+    let output = run_with_input("git", args, "", TOOL_TIMEOUT)?;
+    Ok(if output.trim().is_empty() {
+        "(no output)".to_string()
+    } else {
+        output
+    })
+}
+
+fn git_log(arguments: &Value) -> (String, Result<String, String>) {
+    let count = arguments["count"]
+        .as_u64()
+        .unwrap_or(10)
+        .clamp(1, MAX_LOG_COMMITS)
+        .to_string();
+    let mut args = vec![
+        "log",
+        "-n",
+        count.as_str(),
+        "--date=short",
+        "--format=%h %ad %an: %s",
+    ];
+    if let Some(path) = arguments["path"].as_str() {
+        args.extend(["--", path]);
+    }
+    let display = format!(
+        "git log -n {}{}",
+        count,
+        arguments["path"]
+            .as_str()
+            .map(|p| format!(" -- {}", p))
+            .unwrap_or_default()
+    );
+    (display, git(&args))
+}
+
+fn read_file(arguments: &Value) -> (String, Result<String, String>) {
+    let Some(path) = arguments["path"].as_str() else {
+        return ("read_file".to_string(), Err("missing `path`".to_string()));
+    };
+    let start = arguments["start_line"].as_u64().map(|l| l.max(1) as usize);
+    let end = arguments["end_line"].as_u64().map(|l| l as usize);
+    let lines = match (start, end) {
+        (None, None) => None,
+        (start, end) => Some((start.unwrap_or(1), end.unwrap_or(usize::MAX))),
+    };
+    let display = match lines {
+        Some((start, usize::MAX)) => format!("read {}:{}-", path, start),
+        Some((start, end)) => format!("read {}:{}-{}", path, start, end),
+        None => format!("read {}", path),
+    };
+
+    let result = resolve_path(path).and_then(|resolved| {
+        if !resolved.is_file() {
+            return Err(format!("{} is not a file", path));
+        }
+        read_excerpt(&FileRef {
+            path: resolved,
+            lines,
+        })
+        .ok_or_else(|| {
+            format!(
+                "could not read {} as text, or those lines don't exist",
+                path
+            )
+        })
+    });
+    (display, result)
+}
+
+fn list_dir(arguments: &Value) -> (String, Result<String, String>) {
+    let path = arguments["path"].as_str().unwrap_or(".");
+    let result = resolve_path(path).and_then(|resolved| {
+        let mut entries: Vec<String> = fs::read_dir(&resolved)
+            .map_err(|e| format!("{}: {}", path, e))?
+            .filter_map(Result::ok)
+            .filter(|entry| !is_denied(&entry.file_name().to_string_lossy()))
+            .map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if entry.path().is_dir() {
+                    format!("{}/", name)
+                } else {
+                    name
+                }
+            })
+            .collect();
+        entries.sort();
+        let total = entries.len();
+        entries.truncate(MAX_DIR_ENTRIES);
+        if total > MAX_DIR_ENTRIES {
+            entries.push(format!("... and {} more", total - MAX_DIR_ENTRIES));
+        }
+        Ok(if entries.is_empty() {
+            "(empty directory)".to_string()
+        } else {
+            entries.join("\n")
+        })
+    });
+    (format!("ls {}", path), result)
+}
+
+fn which(arguments: &Value) -> (String, Result<String, String>) {
+    let program = arguments["program"].as_str().unwrap_or_default();
+    let display = format!("which {}", program);
+    if !PROGRAM_NAME.is_match(program) {
+        return (display, Err(format!("invalid program name '{}'", program)));
+    }
+    let found: Vec<String> = env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|dir| dir.join(program))
+        .filter(|candidate| candidate.is_file())
+        .map(|candidate| candidate.display().to_string())
+        .collect();
+    let result = if found.is_empty() {
+        Ok(format!("{} not found on PATH", program))
+    } else {
+        Ok(found.join("\n"))
+    };
+    (display, result)
+}
+
+/// Run one tool call from the allowlist; anything else is refused, never executed
+pub fn run_tool(name: &str, arguments: &str) -> ToolRun {
+    let arguments: Value = if arguments.trim().is_empty() {
+        json!({})
+    } else {
+        match serde_json::from_str(arguments) {
+            Ok(arguments) => arguments,
+            Err(e) => {
+                return ToolRun {
+                    display: name.to_string(),
+                    output: format!("Error: invalid arguments: {}", e),
+                }
+            }
+        }
+    };
+
+    let (display, result) = match name {
+        "git_status" => (
+            "git status --short --branch".to_string(),
+            git(&["status", "--short", "--branch"]),
+        ),
+        "git_log" => git_log(&arguments),
+        "read_file" => read_file(&arguments),
+        "list_dir" => list_dir(&arguments),
+        "which" => which(&arguments),
+        _ => (name.to_string(), Err(format!("unknown tool '{}'", name))),
+    };
+
+    match result {
+        Ok(output) => ToolRun {
+            display,
+            output: truncate_chars(
+                &redact(output.trim_end()),
+                MAX_TOOL_OUTPUT_CHARS,
+                Keep::Start,
+            ),
+        },
+        Err(e) => ToolRun {
+            display: format!("{} (failed: {})", display, e),
+            output: format!("Error: {}", e),
+        },
+    }
+}
@@ -0,0 +1,194 @@
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::api::Answer;
+use crate::config::{get_data_dir, write_private_file, LogConfig};
+use crate::output::source_label;
+use crate::redact::redact;
+
+// ============================================================================
+// Persistent log of questions and answers
+// ============================================================================
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LogEntry {
+    pub id: u64,
+    /// Unix timestamp (seconds)
+    pub timestamp: i64,
+    pub cwd: String,
+    pub question: String,
+    /// History and other context sent along, with secrets redacted
+    pub context: String,
+    /// "llm", "cache", "fix-library" or "rule:<name>"
+    pub source: String,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub response: String,
+    pub latency_ms: u64,
+    /// Normalised error signature, used to file rated answers in the fix library
+    #[serde(default)]
+    pub signature: String,
+}
+
+/// What `call_llm` hands over to be logged
+pub struct NewLogEntry<'a> {
+    pub question: &'a str,
+    pub context: &'a str,
+    pub signature: &'a str,
+    pub answer: &'a Answer,
+}
+
+pub fn get_log_path() -> PathBuf {
+    get_data_dir().join("log.jsonl")
+}
+
+/// Read all entries, oldest first. Unparseable lines are skipped.
+pub fn read_log() -> Result<Vec<LogEntry>, Box<dyn std::error::Error>> {
+    let path = get_log_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Append an exchange to the log, trimming the oldest entries past `max_entries`.
+/// Returns the new entry's id.
+pub fn append_log_entry(config: &LogConfig, new: &NewLogEntry) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut entries = read_log()?;
+    let id = entries.last().map(|e| e.id + 1).unwrap_or(1);
+
+    let entry = LogEntry {
+        id,
+        timestamp: Local::now().timestamp(),
+        cwd: env::current_dir()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default(),
+        question: redact(new.question),
+        context: redact(new.context),
+        source: source_label(&new.answer.source),
+        provider: new.answer.provider.clone(),
+        model: new.answer.model.clone(),
+        response: new.answer.text.clone(),
+        latency_ms: new.answer.latency_ms,
+        signature: new.signature.to_string(),
+    };
+
+    let path = get_log_path();
+    let max_entries = config.max_entries();
+    if entries.len() >= max_entries {
+        // Rewrite the file without the oldest entries
+        entries.push(entry);
+        let keep = &entries[entries.len() - max_entries..];
+        let mut content = String::new();
+        for e in keep {
+            content.push_str(&serde_json::to_string(e)?);
+            content.push('\n');
+        }
+        write_private_file(&path, &content)?;
+    } else {
+        if !path.exists() {
+            write_private_file(&path, "")?;
+        }
+        let mut file = fs::OpenOptions::new().append(true).open(&path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+    }
+
+    Ok(id)
+}
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+/// Shorten a string to one line of at most `max` characters
+fn one_line(text: &str, max: usize) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    if line.chars().count() > max || text.lines().count() > 1 {
+        let short: String = line.chars().take(max.saturating_sub(1)).collect();
+        format!("{}…", short)
+    } else {
+        line.to_string()
+    }
+}
+
+fn print_summary(entry: &LogEntry) {
+    println!(
+        "#{:<5} {}  {:<50}  {}",
+        entry.id,
+        format_timestamp(entry.timestamp),
+        one_line(&entry.question, 50),
+        one_line(&entry.cwd, 40)
+    );
+}
+
+// ============================================================================
+// `sorry log` commands
+// ============================================================================
+
+pub fn log_list(limit: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = read_log()?;
+    if entries.is_empty() {
+        println!("No entries yet.");
+        return Ok(());
+    }
+    let start = entries.len().saturating_sub(limit);
+    for entry in entries[start..].iter().rev() {
+        print_summary(entry);
+    }
+    Ok(())
+}
+
+pub fn log_show(id: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let entry = read_log()?
+        .into_iter()
+        .find(|e| e.id == id)
+        .ok_or(format!("No log entry #{}.", id))?;
+
+    println!("#{}  {}", entry.id, format_timestamp(entry.timestamp));
+    println!("Directory: {}", entry.cwd);
+    match (&entry.provider, &entry.model) {
+        (Some(provider), Some(model)) => {
+            println!("Answered by: {} / {} ({}, {} ms)", provider, model, entry.source, entry.latency_ms)
+        }
+        _ => println!("Answered by: {} ({} ms)", entry.source, entry.latency_ms),
+    }
+    println!("\nQuestion:\n{}", entry.question);
+    if !entry.context.trim().is_empty() {
+        println!("\nContext:\n{}", entry.context.trim_end());
+    }
+    println!("\nResponse:\n{}", entry.response);
+    Ok(())
+}
+
+pub fn log_search(text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let needle = text.to_lowercase();
+    let matches: Vec<LogEntry> = read_log()?
+        .into_iter()
+        .filter(|e| {
+            e.question.to_lowercase().contains(&needle)
+                || e.context.to_lowercase().contains(&needle)
+                || e.response.to_lowercase().contains(&needle)
+        })
+        .collect();
+
+    if matches.is_empty() {
+        println!("No entries match '{}'.", text);
+        return Ok(());
+    }
+    for entry in matches.iter().rev() {
+        print_summary(entry);
+    }
+    Ok(())
+}
@@ -1,13 +1,46 @@
 mod api;
+mod budget;
+mod cache;
 mod cli;
+mod collectors;
 mod config;
+mod context;
+mod environment;
+mod files;
+mod fixes;
 mod history;
+mod hooks;
+mod investigate;
+mod journal;
+mod output;
+mod probe;
+mod redact;
+mod render;
+mod rules;
+mod runbooks;
+mod secrets;
+mod toolchain;
+mod usage;
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 use std::process;
 
-use api::call_llm;
-use cli::{configure_behaviour, configure_provider_interactive, show_config};
+use api::{call_llm, AnswerSource, AskOptions};
+use cache::cache_clear;
+use cli::{
+    config_get, config_list, config_set, config_unset, configure_behaviour, configure_keystore,
+    configure_provider_from_stdin, configure_provider_interactive, show_config,
+};
+use files::parse_file_arg;
+use fixes::{export_fixes, import_fixes, rate_answer, Rating};
+use config::load_config;
+use context::{capture_tmux, read_piped_input, DEFAULT_PIPED_PROMPT};
+use history::record_command;
+use journal::{log_list, log_search, log_show};
+use output::to_structured;
+use render::print_answer;
+use usage::show_usage;
 
 // ============================================================================
 // CLI definition
@@ -17,7 +50,11 @@ use cli::{configure_behaviour, configure_provider_interactive, show_config};
 #[command(name = "sorry")]
 #[command(about = "Send your mistakes to an LLM and get help")]
 #[command(version)]
+#[command(disable_help_subcommand = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Configure OpenAI (interactive setup)
     #[arg(long = "config-openai")]
     config_openai: bool,
@@ -26,10 +63,18 @@ struct Args {
     #[arg(long = "config-groq")]
     config_groq: bool,
 
+    /// With --config-openai/--config-groq: read the API key from stdin instead of prompting
+    #[arg(long = "api-key-stdin")]
+    api_key_stdin: bool,
+
     /// Configure sorry's behaviour/mood
     #[arg(long = "behaviour")]
     behaviour: bool,
 
+    /// Store a provider's API key in the encrypted keystore
+    #[arg(long = "keystore-set", value_name = "PROVIDER")]
+    keystore_set: Option<String>,
+
     /// Show current configuration (without revealing keys)
     #[arg(long = "show-config")]
     show_config: bool,
@@ -42,11 +87,144 @@ struct Args {
     #[arg(long = "last-commands")]
     last_commands: Option<String>,
 
+    /// Number of history commands to include (overrides config and .sorry.toml)
+    #[arg(long = "history-count", value_name = "N")]
+    history_count: Option<usize>,
+
+    /// Rate the last answer (good/bad); good answers go into your fix library
+    #[arg(long = "rate", value_name = "good|bad")]
+    rate: Option<String>,
+
+    /// With --rate: rate this log entry instead of the last one
+    #[arg(long = "id", requires = "rate")]
+    id: Option<u64>,
+
+    /// Skip the fix library and offline rules, always ask the LLM
+    #[arg(long = "no-local")]
+    no_local: bool,
+
+    /// Ignore cached answers and ask again
+    #[arg(long = "no-cache")]
+    no_cache: bool,
+
+    /// Print the request that would be sent (API key hidden) instead of sending it
+    #[arg(long = "dry-run")]
+    dry_run: bool,
+
+    /// Let the model inspect the repo with read-only tools (git status/log, read files, which) before answering
+    #[arg(long = "investigate")]
+    investigate: bool,
+
+    /// Attach a file, or lines of it, to the prompt: path, path:42 or path:40-60 (repeatable)
+    #[arg(long = "file", value_name = "PATH[:RANGE]")]
+    files: Vec<String>,
+
+    /// Include the current tmux pane's scrollback (last N lines, default 200) as captured output
+    #[arg(
+        long = "from-tmux",
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "200"
+    )]
+    from_tmux: Option<usize>,
+
+    /// Output format: plain text, or a JSON object for scripts and editor plugins
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
     /// The prompt to send to the LLM
     #[arg(trailing_var_arg = true)]
     prompt: Vec<String>,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Read or change settings without interactive prompts
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// List past questions and answers
+    Log {
+        #[command(subcommand)]
+        action: Option<LogAction>,
+
+        /// Number of recent entries to list
+        #[arg(short = 'n', long = "limit", default_value_t = 20)]
+        limit: usize,
+    },
+    /// Share your library of 👍'd fixes
+    Fixes {
+        #[command(subcommand)]
+        action: FixesAction,
+    },
+    /// Show token usage and spend per day, week and month
+    Usage,
+    /// Manage the response cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Record a finished command with its directory and exit status (used by the shell hooks)
+    #[command(hide = true)]
+    Record {
+        /// Shell process id
+        #[arg(long)]
+        session: Option<String>,
+        /// Directory the command ran in
+        #[arg(long)]
+        cwd: String,
+        #[arg(long = "exit")]
+        exit_status: Option<i32>,
+        #[arg(allow_hyphen_values = true)]
+        command: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum LogAction {
+    /// Show one exchange in full
+    Show { id: u64 },
+    /// Find exchanges whose question, context or answer contains the text
+    Search { text: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum FixesAction {
+    /// Write the fix library as JSON (to stdout unless --output is given)
+    Export {
+        #[arg(short = 'o', long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// Merge a teammate's exported fixes into your library
+    Import { file: PathBuf },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    /// Delete all cached answers
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print a setting, e.g. `sorry config get providers.openai.model`
+    Get { key: String },
+    /// Change a setting, e.g. `sorry config set network.request_timeout_secs 30`
+    Set { key: String, value: String },
+    /// Remove a setting, e.g. `sorry config unset providers.openai.temperature`
+    Unset { key: String },
+    /// Print the whole config (API keys hidden)
+    List,
+}
+
 // ============================================================================
 // Main
 // ============================================================================
@@ -54,18 +232,116 @@ struct Args {
 fn main() {
     let args = Args::parse();
 
-    // Handle --config-openai
-    if args.config_openai {
-        if let Err(e) = configure_provider_interactive("openai") {
+    // Handle `sorry record` from the shell hooks, before anything slower
+    if let Some(Command::Record {
+        session,
+        cwd,
+        exit_status,
+        command,
+    }) = &args.command
+    {
+        if let Err(e) = record_command(session.as_deref(), cwd, command, *exit_status) {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Handle `sorry config ...`
+    if let Some(Command::Config { action }) = &args.command {
+        let result = match action {
+            ConfigAction::Get { key } => config_get(key),
+            ConfigAction::Set { key, value } => config_set(key, value),
+            ConfigAction::Unset { key } => config_unset(key),
+            ConfigAction::List => config_list(),
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Handle `sorry log ...`
+    if let Some(Command::Log { action, limit }) = &args.command {
+        let result = match action {
+            None => log_list(*limit),
+            Some(LogAction::Show { id }) => log_show(*id),
+            Some(LogAction::Search { text }) => log_search(text),
+        };
+        if let Err(e) = result {
             eprintln!("Error: {}", e);
             process::exit(1);
         }
         return;
     }
 
-    // Handle --config-groq
-    if args.config_groq {
-        if let Err(e) = configure_provider_interactive("groq") {
+    // Handle `sorry fixes ...`
+    if let Some(Command::Fixes { action }) = &args.command {
+        let result = match action {
+            FixesAction::Export { output } => export_fixes(output.as_deref()),
+            FixesAction::Import { file } => import_fixes(file),
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Handle --rate, or the `sorry 👍` / `sorry 👎` shortcuts
+    let rating = match (&args.rate, args.prompt.as_slice()) {
+        (Some(value), _) => Some(Rating::parse(value).unwrap_or_else(|| {
+            eprintln!("Error: --rate expects 'good' or 'bad'");
+            process::exit(1);
+        })),
+        (None, [only]) if only == "👍" || only == "👎" => Rating::parse(only),
+        _ => None,
+    };
+    if let Some(rating) = rating {
+        if let Err(e) = rate_answer(rating, args.id) {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Handle `sorry usage`
+    if let Some(Command::Usage) = &args.command {
+        if let Err(e) = load_config().and_then(|config| show_usage(config.usage.monthly_cap)) {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Handle `sorry cache clear`
+    if let Some(Command::Cache { action: CacheAction::Clear }) = &args.command {
+        match cache_clear() {
+            Ok(removed) => println!("✓ Removed {} cached answers", removed),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // Handle --config-openai / --config-groq
+    let configure_provider = if args.config_openai {
+        Some("openai")
+    } else if args.config_groq {
+        Some("groq")
+    } else {
+        None
+    };
+    if let Some(provider) = configure_provider {
+        let result = if args.api_key_stdin {
+            configure_provider_from_stdin(provider)
+        } else {
+            configure_provider_interactive(provider)
+        };
+        if let Err(e) = result {
             eprintln!("Error: {}", e);
             process::exit(1);
         }
@@ -81,27 +357,135 @@ fn main() {
         return;
     }
 
+    // Handle --keystore-set
+    if let Some(provider) = &args.keystore_set {
+        if let Err(e) = configure_keystore(provider) {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
     // Handle --show-config
     if args.show_config {
-        show_config();
+        if let Err(e) = show_config() {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
         return;
     }
 
-    // Normal path: send prompt to LLM
-    if args.prompt.is_empty() {
+    // Normal path: send prompt to LLM, with anything piped in on stdin or captured from tmux
+    let mut input = match read_piped_input() {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Error: failed to read stdin: {}", e);
+            process::exit(1);
+        }
+    };
+
+    if let Some(lines) = args.from_tmux {
+        if input.is_some() {
+            eprintln!("Error: --from-tmux can't be combined with piped input");
+            process::exit(1);
+        }
+        let captured = load_config()
+            .and_then(|config| capture_tmux(lines, config.context.prompt_pattern.as_deref()));
+        match captured {
+            Ok(captured) => input = Some(captured),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if args.prompt.is_empty() && input.is_none() {
         eprintln!("Usage: sorry <your message about what went wrong>");
         eprintln!("       sorry --config-openai");
         eprintln!("       sorry --config-groq");
+        eprintln!("       sorry config get|set|unset <key> [value]");
         eprintln!("       sorry --behaviour");
+        eprintln!("       sorry --keystore-set <provider>");
         eprintln!("       sorry --show-config");
+        eprintln!("       sorry log [show <id> | search <text>]");
+        eprintln!("       sorry --rate good|bad");
+        eprintln!("       sorry fixes export|import");
+        eprintln!("       sorry usage");
+        eprintln!("       sorry cache clear");
+        eprintln!("       <command> 2>&1 | sorry [message]");
         process::exit(1);
     }
 
-    let prompt = args.prompt.join(" ");
+    let prompt = if args.prompt.is_empty() {
+        DEFAULT_PIPED_PROMPT.to_string()
+    } else {
+        args.prompt.join(" ")
+    };
+
+    let files = match args.files.iter().map(|f| parse_file_arg(f)).collect() {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let options = AskOptions {
+        last_commands: args.last_commands.clone(),
+        history_count: args.history_count,
+        no_local: args.no_local,
+        no_cache: args.no_cache,
+        dry_run: args.dry_run,
+        investigate: args.investigate,
+        json: args.format == OutputFormat::Json,
+        input,
+        files,
+    };
+
+    if options.dry_run {
+        match call_llm(&prompt, &options) {
+            Ok(answer) => println!("{}", answer.text),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if options.json {
+        match call_llm(&prompt, &options) {
+            Ok(answer) => match serde_json::to_string_pretty(&to_structured(&answer)) {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    println!("{}", serde_json::json!({ "error": e.to_string() }));
+                    process::exit(1);
+                }
+            },
+            Err(e) => {
+                println!("{}", serde_json::json!({ "error": e.to_string() }));
+                process::exit(1);
+            }
+        }
+        return;
+    }
 
-    match call_llm(&prompt, args.last_commands.as_deref()) {
-        Ok(response) => {
-            println!("{}", response);
+    match call_llm(&prompt, &options) {
+        Ok(answer) => {
+            print_answer(&answer.text);
+            match &answer.source {
+                AnswerSource::Llm | AnswerSource::DryRun => {}
+                AnswerSource::Cache => {
+                    eprintln!("\n(cached answer; use --no-cache to ask again)");
+                }
+                AnswerSource::FixLibrary => {
+                    eprintln!("\n(from your fix library; use --no-local to ask the LLM)");
+                }
+                AnswerSource::LocalRule(name) => {
+                    eprintln!("\n(answered offline by local rule '{}'; use --no-local to ask the LLM)", name);
+                }
+            }
         }
         Err(e) => {
             eprintln!("Error: {}", e);
@@ -109,3 +493,47 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subcommands_parse_after_the_shell_function_flags() {
+        let args = Args::try_parse_from([
+            "sorry",
+            "--shell",
+            "bash",
+            "--last-commands",
+            "ls",
+            "config",
+            "list",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Config {
+                action: ConfigAction::List
+            })
+        ));
+        assert!(args.prompt.is_empty());
+    }
+
+    #[test]
+    fn other_words_are_the_prompt() {
+        let args = Args::try_parse_from([
+            "sorry",
+            "--shell",
+            "bash",
+            "--last-commands",
+            "ls",
+            "why",
+            "did",
+            "config",
+            "fail",
+        ])
+        .unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(args.prompt, ["why", "did", "config", "fail"]);
+    }
+}
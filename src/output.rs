@@ -0,0 +1,277 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+use crate::api::{Answer, AnswerSource};
+use crate::usage::Usage;
+
+// ============================================================================
+// Structured (JSON) output
+// ============================================================================
+
+/// Appended to the system prompt when `--format json` is used
+pub const JSON_PROMPT: &str = r#"
+
+OUTPUT FORMAT:
+Ignore the plain text rule above. Respond with ONLY a JSON object, no prose around it, shaped like:
+{"explanation": "what went wrong and how to fix it, in your personality", "suggested_commands": [{"command": "git pull --rebase", "risk": "safe"}]}
+"risk" is one of "safe" (read-only or easily undone), "caution" (changes state but recoverable) or "dangerous" (can lose data or rewrite shared history)."#;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum Risk {
+    Safe,
+    Caution,
+    Dangerous,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SuggestedCommand {
+    pub command: String,
+    pub risk: Risk,
+}
+
+/// What `--format json` prints
+#[derive(Debug, Serialize)]
+pub struct StructuredAnswer {
+    pub explanation: String,
+    pub suggested_commands: Vec<SuggestedCommand>,
+    /// "llm", "cache", "fix-library", "rule:<name>" or "dry-run"
+    pub source: String,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub usage: Option<Usage>,
+    pub latency_ms: u64,
+}
+
+/// The shape we ask the model for. `explanation` is required, so stray braces in a
+/// prose answer (`find . -exec rm {} +`) never parse as an empty answer.
+#[derive(Debug, Deserialize)]
+struct ModelJson {
+    explanation: String,
+    #[serde(default)]
+    suggested_commands: Vec<ModelCommand>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ModelCommand {
+    Tagged { command: String, risk: Option<String> },
+    Plain(String),
+}
+
+static DANGEROUS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\brm\s+-[a-z]*[rf]|git\s+reset\s+--hard|git\s+push\b.*(--force|\s-f\b)|git\s+clean\s+-[a-z]*f|git\s+branch\s+-D|git\s+filter-(branch|repo)|\bdd\s+if=|\bmkfs|chmod\s+-R\s+777|drop\s+(table|database)|kubectl\s+delete|docker\s+(system|volume)\s+prune|terraform\s+destroy",
+    )
+    .expect("valid risk pattern")
+});
+
+static CAUTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\bsudo\b|\brm\b|git\s+(pull|rebase|reset|revert|stash\s+(drop|clear)|checkout\s+--|restore|merge|cherry-pick|commit\s+--amend|push)|\bkill(all)?\b|\bmv\b|chmod|chown|(npm|pnpm|yarn|pip)\s+(uninstall|remove)|kubectl\s+(apply|scale|rollout)|docker\s+(rm|stop|kill)|terraform\s+apply",
+    )
+    .expect("valid risk pattern")
+});
+
+/// Heuristic risk of a shell command
+pub fn classify_risk(command: &str) -> Risk {
+    if DANGEROUS.is_match(command) {
+        Risk::Dangerous
+    } else if CAUTION.is_match(command) {
+        Risk::Caution
+    } else {
+        Risk::Safe
+    }
+}
+
+/// Trust the model's tag only if it isn't lower than what the heuristic sees
+fn merge_risk(model_risk: Option<&str>, command: &str) -> Risk {
+    let heuristic = classify_risk(command);
+    let model = match model_risk.map(|r| r.to_lowercase()) {
+        Some(r) if r == "dangerous" => Some(Risk::Dangerous),
+        Some(r) if r == "caution" => Some(Risk::Caution),
+        Some(r) if r == "safe" => Some(Risk::Safe),
+        _ => None,
+    };
+    match model {
+        Some(model) if model > heuristic => model,
+        _ => heuristic,
+    }
+}
+
+static JSON_FENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)```(?i:json)[ \t]*\n(.*?)\n[ \t]*```").expect("valid JSON fence pattern")
+});
+
+/// `$ git pull --rebase`: an explicit shell prompt marks a command
+static PROMPT_LINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*\$\s+(\S.*)$").expect("valid prompt line pattern"));
+
+/// Fence languages whose lines are all commands (`console` blocks mix in output, so only `$` lines count)
+const SHELL_FENCES: &[&str] = &["", "sh", "bash", "zsh", "shell", "fish"];
+
+/// The whole text as JSON, or a fenced ```json block (models sometimes wrap JSON in prose)
+fn parse_model_json(text: &str) -> Option<ModelJson> {
+    if let Ok(parsed) = serde_json::from_str(text.trim()) {
+        return Some(parsed);
+    }
+    let captures = JSON_FENCE.captures(text)?;
+    serde_json::from_str(captures[1].trim()).ok()
+}
+
+static COMMAND_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?:\$\s+|>\s+|(?i:run|try|then|use):?\s+)?((?:sudo\s+)?(?:git|npm|npx|pnpm|yarn|cargo|rustup|docker|kubectl|helm|terraform|pip3?|python3?|uv|poetry|go|make|brew|apt(?:-get)?|cd|ls|rm|mv|cp|mkdir|chmod|chown|export|ssh|curl|kill|systemctl|source)\b.*)$",
+    )
+    .expect("valid command pattern")
+});
+
+/// Whether a line (or inline code span) looks like a shell command
+pub fn looks_like_command(text: &str) -> bool {
+    COMMAND_LINE.is_match(text.trim())
+}
+
+/// Pull commands out of plain text. Only explicit markers count, never prose that merely
+/// starts with a command word: lines of shell code blocks, and lines starting with `$ `.
+pub fn extract_commands(text: &str) -> Vec<String> {
+    let mut commands: Vec<String> = Vec::new();
+    let mut push = |cmd: &str| {
+        let cmd = cmd.trim().to_string();
+        if !cmd.is_empty() && !commands.contains(&cmd) {
+            commands.push(cmd);
+        }
+    };
+
+    // Language of the code block we're in, if any
+    let mut fence: Option<String> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(language) = trimmed.strip_prefix("```") {
+            fence = match fence {
+                Some(_) => None,
+                None => Some(language.trim().to_lowercase()),
+            };
+            continue;
+        }
+        if let Some(prompt) = PROMPT_LINE.captures(line) {
+            push(&prompt[1]);
+        } else if fence.as_deref().is_some_and(|l| SHELL_FENCES.contains(&l)) && !trimmed.starts_with('#') {
+            push(line);
+        }
+    }
+    commands
+}
+
+/// Convert any answer (JSON from the model, or plain text) into the structured form
+pub fn to_structured(answer: &Answer) -> StructuredAnswer {
+    let (explanation, suggested_commands) = match parse_model_json(&answer.text) {
+        Some(parsed) => {
+            let commands = parsed
+                .suggested_commands
+                .into_iter()
+                .map(|c| match c {
+                    ModelCommand::Tagged { command, risk } => SuggestedCommand {
+                        risk: merge_risk(risk.as_deref(), &command),
+                        command,
+                    },
+                    ModelCommand::Plain(command) => SuggestedCommand {
+                        risk: classify_risk(&command),
+                        command,
+                    },
+                })
+                .collect();
+            (parsed.explanation, commands)
+        }
+        None => {
+            let commands = extract_commands(&answer.text)
+                .into_iter()
+                .map(|command| SuggestedCommand {
+                    risk: classify_risk(&command),
+                    command,
+                })
+                .collect();
+            (answer.text.trim().to_string(), commands)
+        }
+    };
+
+    StructuredAnswer {
+        explanation,
+        suggested_commands,
+        source: source_label(&answer.source),
+        provider: answer.provider.clone(),
+        model: answer.model.clone(),
+        usage: answer.usage,
+        latency_ms: answer.latency_ms,
+    }
+}
+
+/// Short label for where an answer came from, shared by the log and JSON output
+pub fn source_label(source: &AnswerSource) -> String {
+    match source {
+        AnswerSource::Llm => "llm".to_string(),
+        AnswerSource::Cache => "cache".to_string(),
+        AnswerSource::LocalRule(name) => format!("rule:{}", name),
+        AnswerSource::FixLibrary => "fix-library".to_string(),
+        AnswerSource::DryRun => "dry-run".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_plain_and_fenced_json() {
+        let plain = r#"{"explanation": "Rebase first.", "suggested_commands": ["git pull --rebase"]}"#;
+        assert_eq!(parse_model_json(plain).unwrap().explanation, "Rebase first.");
+
+        let fenced = "Here you go:\n```json\n{\"explanation\": \"Rebase first.\"}\n```\n";
+        let parsed = parse_model_json(fenced).unwrap();
+        assert_eq!(parsed.explanation, "Rebase first.");
+        assert!(parsed.suggested_commands.is_empty());
+    }
+
+    #[test]
+    fn prose_with_braces_is_not_json() {
+        assert!(parse_model_json("Delete them with find . -name '*.orig' -exec rm {} +").is_none());
+        assert!(parse_model_json("Use {} as the placeholder, e.g. {\"a\": 1}").is_none());
+        assert!(parse_model_json(r#"{"suggested_commands": []}"#).is_none());
+    }
+
+    #[test]
+    fn prose_answer_keeps_its_text() {
+        let answer = Answer {
+            text: "Run find . -exec rm {} + to clean up.".to_string(),
+            source: AnswerSource::Llm,
+            provider: None,
+            model: None,
+            usage: None,
+            latency_ms: 0,
+        };
+        let structured = to_structured(&answer);
+        assert_eq!(structured.explanation, answer.text);
+    }
+
+    #[test]
+    fn extracts_commands_from_fences_and_prompt_lines() {
+        let text = "The remote moved on.\n$ git pull --rebase\nThen:\n```bash\n# push again\ngit push\n```\n";
+        assert_eq!(extract_commands(text), ["git pull --rebase", "git push"]);
+
+        let console = "```console\n$ cargo build\nerror[E0308]: mismatched types\n```";
+        assert_eq!(extract_commands(console), ["cargo build"]);
+    }
+
+    #[test]
+    fn ignores_prose_that_starts_with_a_command_word() {
+        let text = "Then go to the repo settings.\nRun the tests again.\nTry git pull first.";
+        assert!(extract_commands(text).is_empty());
+    }
+
+    #[test]
+    fn risk_never_goes_below_the_heuristic() {
+        assert_eq!(classify_risk("git status"), Risk::Safe);
+        assert_eq!(merge_risk(Some("safe"), "git push --force"), Risk::Dangerous);
+        assert_eq!(merge_risk(Some("dangerous"), "git status"), Risk::Dangerous);
+    }
+}
@@ -0,0 +1,130 @@
+use std::io::{Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// ============================================================================
+// Running external commands with a timeout
+// ============================================================================
+
+/// How often a running command is checked for completion
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Read a pipe on a thread, chunk by chunk, so a chatty command can't block on a full pipe
+fn spawn_reader(mut pipe: impl Read + Send + 'static) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut chunk = [0u8; 8192];
+        while let Ok(read) = pipe.read(&mut chunk) {
+            if read == 0 || sender.send(chunk[..read].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// What a reader got until the pipe closed or `deadline` passed. A background process
+/// the command started (`&`, a daemon) can hold the pipe open after the command exits;
+/// its output is cut off at the deadline instead of blocking sorry.
+fn collect_output(receiver: &Receiver<Vec<u8>>, deadline: Instant) -> String {
+    let mut bytes = Vec::new();
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(left) {
+            Ok(chunk) => bytes.extend_from_slice(&chunk),
+            Err(_) => break,
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Wait for the child until `deadline`, killing it if it is still running then
+fn wait_until(child: &mut Child, deadline: Instant) -> Option<ExitStatus> {
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) if Instant::now() < deadline => thread::sleep(POLL_INTERVAL),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+}
+
+/// Run a command and return its trimmed stdout, or `None` if it is missing,
+/// fails, or is still running after `timeout` (it is killed then).
+pub fn run_with_timeout(program: &str, args: &[&str], timeout: Duration) -> Option<String> {
+    let deadline = Instant::now() + timeout;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let stdout = spawn_reader(child.stdout.take()?);
+    let status = wait_until(&mut child, deadline)?;
+    let output = collect_output(&stdout, deadline);
+    status.success().then(|| output.trim().to_string())
+}
+
+/// First line of a command's output, e.g. for `--version`
+pub fn first_line(program: &str, args: &[&str], timeout: Duration) -> Option<String> {
+    run_with_timeout(program, args, timeout)?
+        .lines()
+        .next()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+}
+
+/// Run a command with `input` on stdin and return its stdout. Unlike
+/// `run_with_timeout`, failures are described so they can be reported.
+pub fn run_with_input(
+    program: &str,
+    args: &[&str],
+    input: &str,
+    timeout: Duration,
+) -> Result<String, String> {
+    let deadline = Instant::now() + timeout;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to start: {}", e))?;
+
+    // Write and read on threads so neither side can fill a pipe and stall the other.
+    // The writer is never joined: a process that keeps stdin open without reading it
+    // would block it, and it ends on its own once the pipe closes.
+    let mut stdin = child.stdin.take().ok_or("no stdin")?;
+    let input = input.to_string();
+    thread::spawn(move || {
+        // A command that ignores its input closes the pipe early; that's fine
+        let _ = stdin.write_all(input.as_bytes());
+    });
+    let stdout = spawn_reader(child.stdout.take().ok_or("no stdout")?);
+    let stderr = spawn_reader(child.stderr.take().ok_or("no stderr")?);
+
+    let status = wait_until(&mut child, deadline)
+        .ok_or_else(|| format!("timed out after {}s", timeout.as_secs()))?;
+    let output = collect_output(&stdout, deadline);
+    let errors = collect_output(&stderr, deadline);
+    if !status.success() {
+        let code = status
+            .code()
+            .map_or_else(|| "a signal".to_string(), |c| format!("status {}", c));
+        let detail = errors.trim().lines().last().unwrap_or_default().to_string();
+        return Err(if detail.is_empty() {
+            format!("exited with {}", code)
+        } else {
+            format!("exited with {}: {}", code, detail)
+        });
+    }
+    Ok(output)
+}
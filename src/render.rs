@@ -0,0 +1,235 @@
+use regex::Regex;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::LazyLock;
+
+use crate::output::looks_like_command;
+
+// ============================================================================
+// Terminal rendering of answers
+// ============================================================================
+
+const BOLD: &str = "\x1b[1m";
+const COMMAND: &str = "\x1b[1;32m";
+const CODE: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Narrowest width we wrap to, however small the terminal claims to be
+const MIN_WIDTH: usize = 20;
+
+pub struct RenderStyle {
+    pub width: usize,
+    pub color: bool,
+}
+
+impl RenderStyle {
+    /// Wrap and colour only when stdout is a terminal; `None` means print the text as is
+    pub fn detect() -> Option<RenderStyle> {
+        if !io::stdout().is_terminal() {
+            return None;
+        }
+        let width = terminal_size::terminal_size()
+            .map(|(terminal_size::Width(w), _)| w as usize)
+            .or_else(|| env::var("COLUMNS").ok().and_then(|c| c.parse().ok()))
+            .unwrap_or(80)
+            .max(MIN_WIDTH);
+        let no_color = env::var("NO_COLOR").is_ok_and(|v| !v.is_empty())
+            || env::var("TERM").is_ok_and(|t| t == "dumb");
+        Some(RenderStyle {
+            width,
+            color: !no_color,
+        })
+    }
+
+    fn paint(&self, text: &str, code: &str) -> String {
+        if self.color {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Print an answer: rendered on a terminal, untouched when piped
+pub fn print_answer(text: &str) {
+    match RenderStyle::detect() {
+        Some(style) => print!("{}", render(text, &style)),
+        None => println!("{}", text),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Span {
+    Plain,
+    Bold,
+    Code,
+}
+
+static INLINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"`([^`]+)`|\*\*([^*]+)\*\*").expect("valid inline markdown pattern")
+});
+
+static LIST_ITEM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\s*)([-*+]|\d+[.)])\s+(.*)$").expect("valid list item pattern")
+});
+
+static HEADER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^#{1,6}\s+(.*)$").expect("valid header pattern"));
+
+/// Split a line into words, keeping inline code spans whole so commands don't break
+fn words(line: &str) -> Vec<(String, Span)> {
+    fn push_plain(text: &str, span: Span, words: &mut Vec<(String, Span)>) {
+        for word in text.split_whitespace() {
+            words.push((word.to_string(), span));
+        }
+    }
+
+    let mut words = Vec::new();
+
+    let mut last = 0;
+    for captures in INLINE.captures_iter(line) {
+        let whole = captures.get(0).expect("match");
+        push_plain(&line[last..whole.start()], Span::Plain, &mut words);
+        if let Some(code) = captures.get(1) {
+            words.push((code.as_str().to_string(), Span::Code));
+        } else if let Some(bold) = captures.get(2) {
+            push_plain(bold.as_str(), Span::Bold, &mut words);
+        }
+        last = whole.end();
+    }
+    push_plain(&line[last..], Span::Plain, &mut words);
+    words
+}
+
+/// Greedy word wrap; `first` prefixes the first line, `indent` the rest
+fn wrap(line: &str, first: &str, indent: &str, style: &RenderStyle) -> String {
+    let mut out = String::from(first);
+    let mut column = first.chars().count();
+    let mut at_line_start = true;
+
+    for (word, span) in words(line) {
+        let len = word.chars().count();
+        if !at_line_start && column + 1 + len > style.width {
+            out.push('\n');
+            out.push_str(indent);
+            column = indent.chars().count();
+            at_line_start = true;
+        }
+        if !at_line_start {
+            out.push(' ');
+            column += 1;
+        }
+        let painted = match span {
+            Span::Plain => word,
+            Span::Bold => style.paint(&word, BOLD),
+            Span::Code if looks_like_command(&word) => style.paint(&word, COMMAND),
+            Span::Code => style.paint(&word, CODE),
+        };
+        out.push_str(&painted);
+        column += len;
+        at_line_start = false;
+    }
+    out.push('\n');
+    out
+}
+
+/// Render plain text or simple markdown for a terminal of the given width
+pub fn render(text: &str, style: &RenderStyle) -> String {
+    let mut out = String::new();
+    let mut in_code_block = false;
+
+    for line in text.trim_end().lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            let painted = if looks_like_command(line) {
+                style.paint(line.trim(), COMMAND)
+            } else {
+                style.paint(line, CODE)
+            };
+            out.push_str(&format!("    {}\n", painted));
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            out.push('\n');
+        } else if let Some(command) = trimmed.strip_prefix("$ ") {
+            // Commands marked with `$ ` are never wrapped, so they stay copy-pasteable;
+            // prose that merely starts with a command word is wrapped like any other line
+            out.push_str(&format!("  {}\n", style.paint(command.trim(), COMMAND)));
+        } else if let Some(captures) = HEADER.captures(trimmed) {
+            out.push_str(&format!("{}\n", style.paint(&captures[1], BOLD)));
+        } else if let Some(captures) = LIST_ITEM.captures(line) {
+            let depth = captures[1].len() / 2;
+            let marker = match &captures[2] {
+                "-" | "*" | "+" => "•".to_string(),
+                numbered => numbered.to_string(),
+            };
+            let first = format!("{}  {} ", "  ".repeat(depth), marker);
+            let indent = " ".repeat(first.chars().count());
+            out.push_str(&wrap(&captures[3], &first, &indent, style));
+        } else {
+            out.push_str(&wrap(trimmed, "", "", style));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(width: usize) -> RenderStyle {
+        RenderStyle {
+            width,
+            color: false,
+        }
+    }
+
+    #[test]
+    fn wraps_prose_to_the_width() {
+        let text =
+            "Run git pull --rebase and push again, then check that the branch is up to date!";
+        let rendered = render(text, &plain(30));
+        assert!(rendered.lines().count() > 1);
+        assert!(rendered.lines().all(|l| l.chars().count() <= 30));
+        let words: Vec<&str> = rendered.split_whitespace().collect();
+        assert_eq!(words.join(" "), text);
+    }
+
+    #[test]
+    fn keeps_marked_commands_and_code_blocks_whole() {
+        let command = "git push --force-with-lease origin feature/a-rather-long-branch-name";
+        let rendered = render(&format!("$ {}", command), &plain(30));
+        assert_eq!(rendered, format!("  {}\n", command));
+
+        let rendered = render(&format!("```bash\n{}\n```", command), &plain(30));
+        assert_eq!(rendered, format!("    {}\n", command));
+    }
+
+    #[test]
+    fn renders_lists_and_headers() {
+        let rendered = render("# Fix\n- pull first\n2. then push", &plain(80));
+        assert_eq!(rendered, "Fix\n  • pull first\n  2. then push\n");
+    }
+
+    #[test]
+    fn colours_only_when_enabled() {
+        let text = "## Fix\n$ git pull --rebase\nUse `cargo build` and **retry**";
+        assert!(!render(text, &plain(80)).contains('\x1b'));
+
+        let coloured = render(
+            text,
+            &RenderStyle {
+                width: 80,
+                color: true,
+            },
+        );
+        assert!(coloured.contains(&format!("{}git pull --rebase{}", COMMAND, RESET)));
+        assert!(coloured.contains(&format!("{}cargo build{}", COMMAND, RESET)));
+        assert!(coloured.contains(&format!("{}retry{}", BOLD, RESET)));
+    }
+}
@@ -0,0 +1,111 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::get_config_dir;
+
+// ============================================================================
+// Local fix rules
+// ============================================================================
+
+/// A canned fix answered offline when its pattern matches the context
+#[derive(Debug, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    /// Regex matched against the question, last command and captured output
+    pub pattern: String,
+    pub fix: String,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct RulesFile {
+    #[serde(default, rename = "rule")]
+    rules: Vec<Rule>,
+}
+
+/// A compiled rule, ready to match
+pub struct CompiledRule {
+    pub name: String,
+    pub fix: String,
+    regex: Regex,
+}
+
+/// Built-in rules for common git errors, as (name, pattern, fix)
+const BUILTIN_RULES: &[(&str, &str, &str)] = &[
+    (
+        "git-non-fast-forward",
+        r"(?i)non-fast-forward|\[rejected\].*\(fetch first\)|updates were rejected because the (tip|remote)",
+        "The remote has commits you don't have yet, so git refused the push.\n\
+         $ git pull --rebase\n\
+         That replays your commits on top of the remote ones. Fix any conflicts, then git push again.",
+    ),
+    (
+        "git-detached-head",
+        r"(?i)detached head|head detached at|you are in 'detached head' state",
+        "You're in detached HEAD state, so new commits don't belong to any branch.\n\
+         To keep your work:\n\
+         $ git switch -c my-branch\n\
+         To go back to a branch and drop it:\n\
+         $ git switch main",
+    ),
+    (
+        "git-unrelated-histories",
+        r"(?i)refusing to merge unrelated histories",
+        "The two branches share no common commit (often a fresh repo with its own README).\n\
+         $ git pull origin main --allow-unrelated-histories\n\
+         Then resolve any conflicts and commit.",
+    ),
+    (
+        "git-no-upstream",
+        r"(?i)has no upstream branch",
+        "Your branch isn't tracking a remote branch yet.\n\
+         $ git push -u origin HEAD\n\
+         That pushes it and sets the upstream so plain git push works next time.",
+    ),
+];
+
+pub fn get_rules_path() -> PathBuf {
+    get_config_dir().join("rules.toml")
+}
+
+/// Load user rules (first, so they win) followed by the built-in ones
+pub fn load_rules() -> Result<Vec<CompiledRule>, Box<dyn std::error::Error>> {
+    let path = get_rules_path();
+    let user_rules = if path.exists() {
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        toml::from_str::<RulesFile>(&content)
+            .map_err(|e| format!("Failed to parse {}:\n{}", path.display(), e))?
+            .rules
+    } else {
+        Vec::new()
+    };
+
+    let builtin = BUILTIN_RULES.iter().map(|(name, pattern, fix)| Rule {
+        name: name.to_string(),
+        pattern: pattern.to_string(),
+        fix: fix.to_string(),
+    });
+
+    user_rules
+        .into_iter()
+        .chain(builtin)
+        .map(|rule| {
+            let regex = Regex::new(&rule.pattern)
+                .map_err(|e| format!("Invalid pattern in rule '{}': {}", rule.name, e))?;
+            Ok(CompiledRule {
+                name: rule.name,
+                fix: rule.fix,
+                regex,
+            })
+        })
+        .collect()
+}
+
+/// Return the first rule whose pattern matches any of the given texts
+pub fn find_matching_rule<'a>(rules: &'a [CompiledRule], texts: &[&str]) -> Option<&'a CompiledRule> {
+    rules
+        .iter()
+        .find(|rule| texts.iter().any(|text| rule.regex.is_match(text)))
+}
@@ -0,0 +1,107 @@
+use std::fs;
+use std::path::Path;
+
+// ============================================================================
+// Team runbooks
+// ============================================================================
+
+/// A Markdown snippet describing a team convention or known fix.
+///
+/// Files may start with front matter listing trigger keywords:
+///
+/// ```text
+/// ---
+/// keywords: kubectl, kube context, wrong cluster
+/// ---
+/// Always run `kubectx` before touching prod...
+/// ```
+///
+/// Runbooks without keywords are always included.
+#[derive(Debug, Clone)]
+pub struct Runbook {
+    pub name: String,
+    pub keywords: Vec<String>,
+    pub body: String,
+}
+
+impl Runbook {
+    fn parse(name: &str, content: &str) -> Runbook {
+        let mut keywords = Vec::new();
+        let mut body = content;
+
+        if let Some(rest) = content.strip_prefix("---\n") {
+            if let Some(end) = rest.find("\n---") {
+                for line in rest[..end].lines() {
+                    if let Some(value) = line.trim().strip_prefix("keywords:") {
+                        keywords.extend(
+                            value
+                                .split(',')
+                                .map(|k| k.trim().trim_matches('"').to_lowercase())
+                                .filter(|k| !k.is_empty()),
+                        );
+                    }
+                }
+                body = rest[end + 4..].trim_start_matches('-');
+            }
+        }
+
+        Runbook {
+            name: name.to_string(),
+            keywords,
+            body: body.trim().to_string(),
+        }
+    }
+
+    /// Whether this runbook applies to the given (lowercased) context
+    fn matches(&self, haystack: &str) -> bool {
+        self.keywords.is_empty() || self.keywords.iter().any(|k| haystack.contains(k.as_str()))
+    }
+}
+
+/// Load every `*.md` file in the runbooks directory, sorted by file name
+pub fn load_runbooks(dir: &Path) -> Result<Vec<Runbook>, Box<dyn std::error::Error>> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read runbooks directory {}: {}", dir.display(), e))?;
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .collect();
+    paths.sort();
+
+    let mut runbooks = Vec::new();
+    for path in paths {
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read runbook {}: {}", path.display(), e))?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let runbook = Runbook::parse(&name, &content.replace("\r\n", "\n"));
+        if !runbook.body.is_empty() {
+            runbooks.push(runbook);
+        }
+    }
+    Ok(runbooks)
+}
+
+/// Pick the runbooks relevant to the question and recent commands
+pub fn select_runbooks<'a>(runbooks: &'a [Runbook], question: &str, commands: &[String]) -> Vec<&'a Runbook> {
+    let haystack = format!("{}\n{}", question, commands.join("\n")).to_lowercase();
+    runbooks.iter().filter(|r| r.matches(&haystack)).collect()
+}
+
+/// Format selected runbooks for the system prompt
+pub fn format_runbooks(runbooks: &[&Runbook]) -> String {
+    if runbooks.is_empty() {
+        return String::new();
+    }
+
+    let mut section = String::from(
+        "\n\nTEAM RUNBOOKS (internal conventions; prefer these over generic advice when relevant):",
+    );
+    for runbook in runbooks {
+        section.push_str(&format!("\n\n[{}]\n{}", runbook.name, runbook.body));
+    }
+    section
+}
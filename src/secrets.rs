@@ -0,0 +1,242 @@
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::config::{get_config_dir, write_private_file, ProviderConfig};
+
+/// Environment variable holding the keystore passphrase (prompted for if unset)
+pub const KEYSTORE_PASSPHRASE_ENV: &str = "SORRY_KEYSTORE_PASSPHRASE";
+
+const KDF_ROUNDS: u32 = 310_000;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+// ============================================================================
+// API key resolution
+// ============================================================================
+
+/// Resolve the API key for a provider.
+/// Order: `api_key_env`, then `api_key_cmd`, then the keystore, then the plaintext `api_key`.
+pub fn resolve_api_key(
+    provider_name: &str,
+    provider: &ProviderConfig,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(var) = &provider.api_key_env {
+        let key = env::var(var).map_err(|_| {
+            format!(
+                "Environment variable '{}' (api_key_env for '{}') is not set.",
+                var, provider_name
+            )
+        })?;
+        return non_empty(key.trim().to_string(), provider_name);
+    }
+
+    if let Some(cmd) = &provider.api_key_cmd {
+        return non_empty(run_key_command(cmd)?, provider_name);
+    }
+
+    if provider.api_key_keystore {
+        let key = keystore_get(provider_name)?.ok_or(format!(
+            "No key for '{}' in the keystore. Run 'sorry --keystore-set {}' first.",
+            provider_name, provider_name
+        ))?;
+        return non_empty(key, provider_name);
+    }
+
+    non_empty(provider.api_key.clone(), provider_name)
+}
+
+/// Human-readable description of where a provider's key comes from
+pub fn describe_key_source(provider: &ProviderConfig) -> String {
+    if let Some(var) = &provider.api_key_env {
+        format!("from environment variable ${}", var)
+    } else if let Some(cmd) = &provider.api_key_cmd {
+        format!("from command '{}'", cmd)
+    } else if provider.api_key_keystore {
+        "from encrypted keystore".to_string()
+    } else if provider.api_key.is_empty() {
+        "not set".to_string()
+    } else {
+        "configured (hidden, stored in plaintext)".to_string()
+    }
+}
+
+fn non_empty(key: String, provider_name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    if key.is_empty() {
+        return Err(format!(
+            "API key not set for provider '{}'. Run 'sorry --config-{}' to configure.",
+            provider_name, provider_name
+        )
+        .into());
+    }
+    Ok(key)
+}
+
+fn run_key_command(cmd: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .output()
+        .map_err(|e| format!("Failed to run api_key_cmd '{}': {}", cmd, e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "api_key_cmd '{}' failed ({}): {}",
+            cmd,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    // Like `pass`, only the first line is the secret
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.lines().next().unwrap_or_default().trim().to_string())
+}
+
+// ============================================================================
+// Encrypted keystore
+// ============================================================================
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct Keystore {
+    /// Hex-encoded PBKDF2 salt
+    salt: String,
+    entries: HashMap<String, KeystoreEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KeystoreEntry {
+    nonce: String,
+    ciphertext: String,
+}
+
+pub fn get_keystore_path() -> PathBuf {
+    get_config_dir().join("keystore.json")
+}
+
+fn load_keystore() -> Result<Option<Keystore>, Box<dyn std::error::Error>> {
+    let path = get_keystore_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path)?;
+    let keystore = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    Ok(Some(keystore))
+}
+
+fn read_passphrase() -> Result<String, Box<dyn std::error::Error>> {
+    if let Ok(passphrase) = env::var(KEYSTORE_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase = rpassword::prompt_password("Keystore passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err("Keystore passphrase cannot be empty.".into());
+    }
+    Ok(passphrase)
+}
+
+fn derive_cipher(passphrase: &str, salt: &[u8]) -> ChaCha20Poly1305 {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase.as_bytes(), salt, KDF_ROUNDS, &mut key);
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+/// Decrypt a provider's key from the keystore
+pub fn keystore_get(provider_name: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some(keystore) = load_keystore()? else {
+        return Ok(None);
+    };
+    let Some(entry) = keystore.entries.get(provider_name) else {
+        return Ok(None);
+    };
+
+    let salt = from_hex(&keystore.salt)?;
+    let nonce = from_hex(&entry.nonce)?;
+    let ciphertext = from_hex(&entry.ciphertext)?;
+    if nonce.len() != NONCE_LEN {
+        return Err("Corrupt keystore entry (bad nonce length).".into());
+    }
+
+    let cipher = derive_cipher(&read_passphrase()?, &salt);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| "Failed to decrypt keystore: wrong passphrase or corrupt file.")?;
+
+    Ok(Some(String::from_utf8(plaintext)?))
+}
+
+/// Encrypt and store a provider's key in the keystore
+pub fn keystore_set(provider_name: &str, api_key: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut keystore = load_keystore()?.unwrap_or_default();
+
+    let salt = if keystore.salt.is_empty() {
+        let mut salt = [0u8; SALT_LEN];
+        getrandom::getrandom(&mut salt)?;
+        keystore.salt = to_hex(&salt);
+        salt.to_vec()
+    } else {
+        from_hex(&keystore.salt)?
+    };
+
+    // An existing entry, decrypted below to make sure the passphrase matches
+    let existing = match keystore.entries.values().next() {
+        Some(entry) => {
+            let nonce = from_hex(&entry.nonce)?;
+            if nonce.len() != NONCE_LEN {
+                return Err("Corrupt keystore entry (bad nonce length).".into());
+            }
+            Some((nonce, from_hex(&entry.ciphertext)?))
+        }
+        None => None,
+    };
+
+    let mut nonce = [0u8; NONCE_LEN];
+    getrandom::getrandom(&mut nonce)?;
+
+    let cipher = derive_cipher(&read_passphrase()?, &salt);
+
+    if let Some((existing_nonce, existing_ciphertext)) = existing {
+        cipher
+            .decrypt(
+                Nonce::from_slice(&existing_nonce),
+                existing_ciphertext.as_ref(),
+            )
+            .map_err(|_| "Passphrase does not match the existing keystore.")?;
+    }
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), api_key.as_bytes())
+        .map_err(|_| "Failed to encrypt API key.")?;
+
+    keystore.entries.insert(
+        provider_name.to_string(),
+        KeystoreEntry {
+            nonce: to_hex(&nonce),
+            ciphertext: to_hex(&ciphertext),
+        },
+    );
+
+    write_private_file(&get_keystore_path(), &serde_json::to_string_pretty(&keystore)?)?;
+    Ok(())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !s.is_ascii() || !s.len().is_multiple_of(2) {
+        return Err("Corrupt keystore (invalid hex string).".into());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| e.into()))
+        .collect()
+}
@@ -0,0 +1,241 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// ============================================================================
+// Project type detection
+// ============================================================================
+
+/// Most Makefile targets listed in the summary
+const MAX_MAKE_TARGETS: usize = 10;
+
+/// What was found in the project directory
+#[derive(Debug, Default)]
+pub struct ProjectInfo {
+    /// Short facts for the user message, e.g. "Node.js (pnpm workspace)"
+    pub summary: Vec<String>,
+    /// Instructions for the system prompt, e.g. "Use pnpm, never npm install"
+    pub hints: Vec<String>,
+}
+
+impl ProjectInfo {
+    fn add(&mut self, summary: impl Into<String>, hint: Option<&str>) {
+        self.summary.push(summary.into());
+        if let Some(hint) = hint {
+            self.hints.push(hint.to_string());
+        }
+    }
+}
+
+/// Files whose presence marks a project root
+const MARKERS: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "requirements.txt",
+    "Pipfile",
+    "go.mod",
+    "Makefile",
+    "docker-compose.yml",
+    "docker-compose.yaml",
+    "compose.yml",
+    "compose.yaml",
+];
+
+fn has_terraform(dir: &Path) -> bool {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .any(|e| e.path().extension().is_some_and(|ext| ext == "tf"))
+        })
+        .unwrap_or(false)
+}
+
+fn is_project_dir(dir: &Path) -> bool {
+    MARKERS.iter().any(|m| dir.join(m).is_file()) || has_terraform(dir)
+}
+
+/// Nearest directory at or above `start` that looks like a project, stopping at the repository root
+fn find_project_root(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        if is_project_dir(dir) {
+            return Some(dir.to_path_buf());
+        }
+        if dir.join(".git").exists() {
+            return None;
+        }
+    }
+    None
+}
+
+/// Package manager of a JavaScript project: lockfiles in the project or any parent (workspaces)
+fn node_package_manager(root: &Path, package_json: &str) -> Option<(&'static str, bool)> {
+    for dir in root.ancestors() {
+        let workspace = dir.join("pnpm-workspace.yaml").is_file();
+        let found = if workspace || dir.join("pnpm-lock.yaml").is_file() {
+            Some("pnpm")
+        } else if dir.join("yarn.lock").is_file() {
+            Some("yarn")
+        } else if dir.join("bun.lockb").is_file() || dir.join("bun.lock").is_file() {
+            Some("bun")
+        } else if dir.join("package-lock.json").is_file() {
+            Some("npm")
+        } else {
+            None
+        };
+        if let Some(manager) = found {
+            let workspace = workspace || (dir != root) || package_json.contains("\"workspaces\"");
+            return Some((manager, workspace));
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+
+    // "packageManager": "pnpm@9.1.0" works without a lockfile
+    ["pnpm", "yarn", "bun", "npm"]
+        .into_iter()
+        .find(|m| package_json.contains(&format!("\"packageManager\": \"{}@", m)))
+        .map(|m| (m, package_json.contains("\"workspaces\"")))
+}
+
+fn detect_node(root: &Path, info: &mut ProjectInfo) {
+    let Ok(package_json) = fs::read_to_string(root.join("package.json")) else {
+        return;
+    };
+    match node_package_manager(root, &package_json) {
+        Some((manager, workspace)) => {
+            let kind = if workspace { "workspace" } else { "project" };
+            let hint = match manager {
+                "pnpm" => "This is a pnpm project: use pnpm (pnpm install, pnpm add, pnpm run), never npm install or yarn.",
+                "yarn" => "This is a yarn project: use yarn (yarn install, yarn add), never npm install or pnpm.",
+                "bun" => "This is a bun project: use bun (bun install, bun add, bun run), not npm.",
+                _ => "This is an npm project: use npm (npm install, npm run), not yarn or pnpm.",
+            };
+            info.add(format!("Node.js ({} {})", manager, kind), Some(hint));
+            if workspace {
+                info.hints.push(format!(
+                    "It is a {} workspace: run package scripts from the right workspace package.",
+                    manager
+                ));
+            }
+        }
+        None => info.add("Node.js (package.json, no lockfile)", None),
+    }
+}
+
+fn detect_python(root: &Path, info: &mut ProjectInfo) {
+    let pyproject = fs::read_to_string(root.join("pyproject.toml")).unwrap_or_default();
+    if root.join("uv.lock").is_file() || pyproject.contains("[tool.uv]") {
+        info.add(
+            "Python (uv)",
+            Some("This is a uv project: use uv (uv sync, uv add, uv run), not pip install."),
+        );
+    } else if root.join("poetry.lock").is_file() || pyproject.contains("[tool.poetry]") {
+        info.add(
+            "Python (Poetry)",
+            Some("This is a Poetry project: use poetry (poetry install, poetry add, poetry run), not pip install."),
+        );
+    } else if root.join("Pipfile").is_file() {
+        info.add(
+            "Python (Pipenv)",
+            Some("This is a Pipenv project: use pipenv (pipenv install, pipenv run), not pip install."),
+        );
+    } else if !pyproject.is_empty() {
+        info.add("Python (pyproject.toml)", None);
+    } else if root.join("requirements.txt").is_file() {
+        info.add(
+            "Python (requirements.txt)",
+            Some("Python dependencies are in requirements.txt; install them inside a virtualenv."),
+        );
+    }
+}
+
+fn make_targets(makefile: &str) -> Vec<String> {
+    makefile
+        .lines()
+        .filter_map(|line| {
+            let (target, rest) = line.split_once(':')?;
+            let plain = !target.is_empty()
+                && !target.starts_with(['.', '\t', ' ', '#'])
+                && !rest.starts_with('=')
+                && target.chars().all(|c| c.is_ascii_alphanumeric() || "_-/".contains(c));
+            plain.then(|| target.to_string())
+        })
+        .take(MAX_MAKE_TARGETS)
+        .collect()
+}
+
+/// Detect the project around `cwd`, or `None` outside any recognisable project
+pub fn detect_project(cwd: &Path) -> Option<ProjectInfo> {
+    let root = find_project_root(cwd)?;
+    let mut info = ProjectInfo::default();
+
+    if let Ok(cargo_toml) = fs::read_to_string(root.join("Cargo.toml")) {
+        let kind = if cargo_toml.contains("[workspace]") { "Cargo workspace" } else { "Cargo" };
+        info.add(
+            format!("Rust ({})", kind),
+            Some("This is a Rust project: use cargo (cargo build, cargo add), not other package managers."),
+        );
+    }
+
+    detect_node(&root, &mut info);
+    detect_python(&root, &mut info);
+
+    if let Ok(go_mod) = fs::read_to_string(root.join("go.mod")) {
+        let module = go_mod
+            .lines()
+            .find_map(|l| l.strip_prefix("module "))
+            .map(|m| format!(" {}", m.trim()))
+            .unwrap_or_default();
+        info.add(
+            format!("Go module{}", module),
+            Some("This is a Go module: use go commands (go build, go get, go mod tidy)."),
+        );
+    }
+
+    if let Ok(makefile) = fs::read_to_string(root.join("Makefile")) {
+        let targets = make_targets(&makefile);
+        if targets.is_empty() {
+            info.add("Makefile", None);
+        } else {
+            info.add(format!("Makefile (targets: {})", targets.join(", ")), None);
+        }
+    }
+
+    let compose = ["docker-compose.yml", "docker-compose.yaml", "compose.yml", "compose.yaml"]
+        .into_iter()
+        .find(|f| root.join(f).is_file());
+    if let Some(compose) = compose {
+        info.add(
+            format!("Docker Compose ({})", compose),
+            Some("Services run with Docker Compose: prefer `docker compose` commands for them."),
+        );
+    }
+
+    if has_terraform(&root) {
+        info.add(
+            "Terraform",
+            Some("This directory has Terraform: always suggest terraform plan before terraform apply."),
+        );
+    }
+
+    (!info.summary.is_empty()).then_some(info)
+}
+
+/// Short summary for the user message
+pub fn format_project_summary(info: &ProjectInfo) -> String {
+    format!("Project type: {}\n\n", info.summary.join("; "))
+}
+
+/// Tooling hints for the system prompt
+pub fn format_project_hints(info: &ProjectInfo) -> String {
+    if info.hints.is_empty() {
+        return String::new();
+    }
+    let mut section = String::from("\n\nPROJECT TOOLING:");
+    for hint in &info.hints {
+        section.push_str(&format!("\n- {}", hint));
+    }
+    section
+}
@@ -0,0 +1,218 @@
+use chrono::{DateTime, Datelike, Duration, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::config::{get_data_dir, write_private_file, ProviderConfig};
+
+// ============================================================================
+// Token usage ledger
+// ============================================================================
+
+/// The `usage` object of an OpenAI-compatible response
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LedgerEntry {
+    /// Unix timestamp (seconds)
+    timestamp: i64,
+    provider: String,
+    model: String,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    /// Cost in USD at the prices configured when the call was made
+    cost: f64,
+}
+
+pub fn get_ledger_path() -> PathBuf {
+    get_data_dir().join("usage.jsonl")
+}
+
+/// Cost in USD of a call, using the provider's per-million-token prices
+pub fn cost_of(provider: &ProviderConfig, usage: &Usage) -> f64 {
+    let prompt = provider.prompt_price.unwrap_or(0.0) * usage.prompt_tokens as f64;
+    let completion = provider.completion_price.unwrap_or(0.0) * usage.completion_tokens as f64;
+    (prompt + completion) / 1_000_000.0
+}
+
+fn read_ledger() -> Result<Vec<LedgerEntry>, Box<dyn std::error::Error>> {
+    let path = get_ledger_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Append a call to the ledger
+pub fn record_usage(
+    provider_name: &str,
+    provider: &ProviderConfig,
+    usage: &Usage,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let entry = LedgerEntry {
+        timestamp: Local::now().timestamp(),
+        provider: provider_name.to_string(),
+        model: provider.model.clone(),
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        cost: cost_of(provider, usage),
+    };
+
+    let path = get_ledger_path();
+    if !path.exists() {
+        write_private_file(&path, "")?;
+    }
+    let mut file = fs::OpenOptions::new().append(true).open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+    Ok(())
+}
+
+fn start_of_day(now: DateTime<Local>) -> i64 {
+    let midnight = now.date_naive().and_hms_opt(0, 0, 0).expect("midnight exists");
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|t| t.timestamp())
+        .unwrap_or_else(|| now.timestamp())
+}
+
+fn start_of_week(now: DateTime<Local>) -> i64 {
+    let days_since_monday = now.weekday().num_days_from_monday() as i64;
+    start_of_day(now - Duration::days(days_since_monday))
+}
+
+fn start_of_month(now: DateTime<Local>) -> i64 {
+    let days_since_first = now.day0() as i64;
+    start_of_day(now - Duration::days(days_since_first))
+}
+
+/// Total spend (USD) since the start of the current calendar month
+pub fn month_spend() -> Result<f64, Box<dyn std::error::Error>> {
+    let since = start_of_month(Local::now());
+    Ok(read_ledger()?
+        .iter()
+        .filter(|e| e.timestamp >= since)
+        .map(|e| e.cost)
+        .sum())
+}
+
+/// Why a configured cap can't see what a provider costs, if it can't
+pub fn unpriced_cap_warning(
+    cap: Option<f64>,
+    provider_name: &str,
+    provider: &ProviderConfig,
+) -> Option<String> {
+    cap?;
+    if provider.prompt_price.is_some() || provider.completion_price.is_some() {
+        return None;
+    }
+    Some(format!(
+        "usage.monthly_cap is set but providers.{} has no prompt_price or completion_price, \
+         so its calls cost $0 and never reach the cap",
+        provider_name
+    ))
+}
+
+/// Refuse to call the LLM once the monthly cap is reached
+pub fn check_spend_cap(cap: Option<f64>) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(cap) = cap else {
+        return Ok(());
+    };
+    let spent = month_spend()?;
+    if spent >= cap {
+        return Err(format!(
+            "Monthly spend cap reached: ${:.4} of ${} used. Raise usage.monthly_cap or wait until next month.",
+            spent, cap
+        )
+        .into());
+    }
+    Ok(())
+}
+
+// ============================================================================
+// `sorry usage`
+// ============================================================================
+
+#[derive(Default)]
+struct Totals {
+    calls: u64,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    cost: f64,
+}
+
+impl Totals {
+    fn add(&mut self, entry: &LedgerEntry) {
+        self.calls += 1;
+        self.prompt_tokens += entry.prompt_tokens;
+        self.completion_tokens += entry.completion_tokens;
+        self.cost += entry.cost;
+    }
+
+    fn print(&self, label: &str) {
+        println!(
+            "  {:<24} {:>5} calls  {:>9} in  {:>8} out  ${:.4}",
+            label, self.calls, self.prompt_tokens, self.completion_tokens, self.cost
+        );
+    }
+}
+
+pub fn show_usage(monthly_cap: Option<f64>) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = read_ledger()?;
+    let now = Local::now();
+    let (day, week, month) = (start_of_day(now), start_of_week(now), start_of_month(now));
+
+    let mut today = Totals::default();
+    let mut this_week = Totals::default();
+    let mut this_month = Totals::default();
+    let mut by_model: BTreeMap<String, Totals> = BTreeMap::new();
+
+    for entry in &entries {
+        if entry.timestamp >= day {
+            today.add(entry);
+        }
+        if entry.timestamp >= week {
+            this_week.add(entry);
+        }
+        if entry.timestamp >= month {
+            this_month.add(entry);
+            by_model
+                .entry(format!("{}/{}", entry.provider, entry.model))
+                .or_default()
+                .add(entry);
+        }
+    }
+
+    println!();
+    println!("Usage:");
+    today.print("Today");
+    this_week.print("This week");
+    this_month.print("This month");
+
+    if !by_model.is_empty() {
+        println!();
+        println!("This month by model:");
+        for (model, totals) in &by_model {
+            totals.print(model);
+        }
+    }
+
+    if let Some(cap) = monthly_cap {
+        println!();
+        println!("Monthly cap: ${:.4} of ${} used", this_month.cost, cap);
+    }
+    println!();
+    Ok(())
+}